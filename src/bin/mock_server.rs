@@ -0,0 +1,218 @@
+//! Replays recorded api responses so the launcher can be run and tested offline.
+//!
+//! `cargo run --bin mock_server -- [fixtures dir] [port]`, then start elysia with
+//! `ELYSIA_HOYOPLAY_API_URL=http://127.0.0.1:8080/hoyoplay`,
+//! `ELYSIA_HOYOPLAY_CDN_URL=http://127.0.0.1:8080/cdn`,
+//! `ELYSIA_IMAGE_URL=http://127.0.0.1:8080/images` and
+//! `ELYSIA_KURO_URL=http://127.0.0.1:8080/kuro`.
+//!
+//! A request for `/<path>?game_id=<id>` (or `game_ids[]=<id>`) is answered with the first file found of
+//! `<path>/<id>.json`, `<path>.json` and `<path>`. Every response carries an ETag,
+//! so conditional requests get a 304 like they would from the real services, and
+//! `Range` requests are answered with a 206 so download resume can be tried out.
+//!
+//! The integration tests include this file and call `serve` on port 0.
+
+use std::{
+    env, fs,
+    hash::{DefaultHasher, Hash, Hasher},
+    path::{Component, Path, PathBuf},
+};
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+#[tokio::main]
+async fn main() {
+    let mut args = env::args().skip(1);
+    let root = PathBuf::from(args.next().unwrap_or_else(|| "fixtures".to_string()));
+    let port = args.next().unwrap_or_else(|| "8080".to_string());
+
+    let listener = TcpListener::bind(format!("127.0.0.1:{port}"))
+        .await
+        .unwrap_or_else(|e| panic!("Cannot bind to port {port}: {e}"));
+    // Port 0 picks a free one, so print the one actually bound
+    match listener.local_addr() {
+        Ok(address) => println!("Serving {root:?} on http://{address}"),
+        Err(e) => println!("Serving {root:?}, cannot read the address: {e}"),
+    }
+
+    serve(listener, root).await;
+}
+
+/// Answers requests on `listener` with the fixtures in `root`, forever.
+pub async fn serve(listener: TcpListener, root: PathBuf) {
+    loop {
+        let Ok((stream, _)) = listener.accept().await else {
+            continue;
+        };
+
+        let root = root.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle(stream, &root).await {
+                println!("Error when handling request: {e}");
+            }
+        });
+    }
+}
+
+struct Request {
+    path: String,
+    game_id: Option<String>,
+    if_none_match: Option<String>,
+    range: Option<(u64, Option<u64>)>,
+}
+
+async fn handle(mut stream: TcpStream, root: &Path) -> Result<(), String> {
+    let mut buffer = vec![0; 16 * 1024];
+    let read = stream
+        .read(&mut buffer)
+        .await
+        .map_err(|e| format!("Cannot read request: {e}"))?;
+    let request = parse_request(&String::from_utf8_lossy(&buffer[..read]))?;
+
+    let response = match find_fixture(root, &request).map(fs::read) {
+        Some(Ok(body)) => {
+            let etag = etag(&body);
+            if request.if_none_match.as_deref() == Some(etag.as_str()) {
+                println!("304 {}", request.path);
+                response(304, "Not Modified", &etag, "", &[])
+            } else if let Some((start, end)) = request.range {
+                let end = end.map_or(body.len() as u64, |end| end + 1);
+                if start >= end || end > body.len() as u64 {
+                    println!("416 {}", request.path);
+                    response(416, "Range Not Satisfiable", "", "", &[])
+                } else {
+                    println!("206 {} {start}-{end}", request.path);
+                    response(
+                        206,
+                        "Partial Content",
+                        &etag,
+                        content_type(&request.path),
+                        &body[start as usize..end as usize],
+                    )
+                }
+            } else {
+                println!("200 {}", request.path);
+                response(200, "OK", &etag, content_type(&request.path), &body)
+            }
+        }
+        _ => {
+            println!("404 {}", request.path);
+            response(404, "Not Found", "", "", &[])
+        }
+    };
+
+    stream
+        .write_all(&response)
+        .await
+        .map_err(|e| format!("Cannot write response: {e}"))
+}
+
+fn parse_request(raw: &str) -> Result<Request, String> {
+    let mut lines = raw.lines();
+    let target = lines
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .ok_or_else(|| "Malformed request line".to_string())?;
+
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let game_id = query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| matches!(*key, "game_id" | "game_ids[]" | "game_ids%5B%5D"))
+        .map(|(_, value)| value.to_string());
+    let headers = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.to_ascii_lowercase(), value.trim().to_string()))
+        .collect::<Vec<_>>();
+    let header = |name: &str| {
+        headers
+            .iter()
+            .find(|(header, _)| header == name)
+            .map(|(_, value)| value.clone())
+    };
+    let if_none_match = header("if-none-match");
+    let range = header("range").and_then(|value| parse_range(&value));
+
+    Ok(Request {
+        path: path.replace("%20", " "),
+        game_id,
+        if_none_match,
+        range,
+    })
+}
+
+/// Only single `bytes=<start>-[end]` ranges, which is all the launcher sends.
+fn parse_range(value: &str) -> Option<(u64, Option<u64>)> {
+    let (start, end) = value.strip_prefix("bytes=")?.split_once('-')?;
+    let start = start.parse().ok()?;
+    let end = if end.is_empty() {
+        None
+    } else {
+        Some(end.parse().ok()?)
+    };
+
+    Some((start, end))
+}
+
+fn find_fixture(root: &Path, request: &Request) -> Option<PathBuf> {
+    let relative = Path::new(request.path.trim_start_matches('/'));
+    if relative
+        .components()
+        .any(|component| !matches!(component, Component::Normal(_)))
+    {
+        return None;
+    }
+
+    let base = root.join(relative);
+    let mut candidates = Vec::new();
+    if let Some(game_id) = &request.game_id {
+        candidates.push(base.join(format!("{game_id}.json")));
+    }
+    candidates.push(PathBuf::from(format!("{}.json", base.display())));
+    candidates.push(base);
+
+    candidates.into_iter().find(|path| path.is_file())
+}
+
+fn etag(body: &[u8]) -> String {
+    let mut hasher = DefaultHasher::new();
+    body.hash(&mut hasher);
+
+    format!("\"{:x}\"", hasher.finish())
+}
+
+fn content_type(path: &str) -> &'static str {
+    match Path::new(path)
+        .extension()
+        .and_then(|extension| extension.to_str())
+    {
+        Some("png") => "image/png",
+        Some("webp") => "image/webp",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("zip" | "7z" | "exe") => "application/octet-stream",
+        _ => "application/json",
+    }
+}
+
+fn response(status: u16, reason: &str, etag: &str, content_type: &str, body: &[u8]) -> Vec<u8> {
+    let mut head = format!(
+        "HTTP/1.1 {status} {reason}\r\nContent-Length: {}\r\nAccept-Ranges: bytes\r\nConnection: close\r\n",
+        body.len()
+    );
+    if !etag.is_empty() {
+        head.push_str(&format!("ETag: {etag}\r\n"));
+    }
+    if !content_type.is_empty() {
+        head.push_str(&format!("Content-Type: {content_type}\r\n"));
+    }
+    head.push_str("\r\n");
+
+    let mut response = head.into_bytes();
+    response.extend_from_slice(body);
+
+    response
+}
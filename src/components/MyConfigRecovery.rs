@@ -0,0 +1,122 @@
+use freya::prelude::*;
+
+use crate::{
+    components::MyButton,
+    context::ConfigRecovery,
+    settings::{GlobalSettings, block_saving},
+};
+
+/// Tells that the config couldn't be loaded and offers the last good backup.
+#[component]
+pub fn MyConfigRecovery() -> Element {
+    let mut recovery = use_context::<Signal<Option<ConfigRecovery>>>();
+    let mut settings = use_context::<Signal<GlobalSettings>>();
+
+    let Some(current) = recovery.read().clone() else {
+        return rsx! {};
+    };
+
+    if current.newer_version {
+        return rsx! {
+            rect {
+                width: "fill",
+                padding: "12",
+                spacing: "8",
+                corner_radius: "8",
+                background: "rgb(120, 30, 30, 0.9)",
+
+                label {
+                    font_size: "16",
+                    "Your settings are from a newer version of the launcher, the defaults are used and nothing is saved until you update."
+                }
+                label {
+                    font_size: "12",
+                    "{current.error}"
+                }
+                MyButton {
+                    onpress: move |_| recovery.set(None),
+                    rect {
+                        padding: "4 16",
+                        label { "Hide" }
+                    }
+                }
+            }
+        };
+    }
+
+    let kept = match &current.corrupted {
+        Some(path) => format!("The broken file was kept as {path:?}."),
+        None => "The broken file couldn't be moved, settings aren't saved until you restore a backup or keep the defaults, which replaces it.".to_string(),
+    };
+    let backup_name = current
+        .backup
+        .as_ref()
+        .and_then(|backup| backup.file_name())
+        .map(|name| name.to_string_lossy().into_owned());
+
+    let onrestore = move |_| {
+        let Some(backup) = recovery.peek().as_ref().and_then(|r| r.backup.clone()) else {
+            return;
+        };
+
+        match GlobalSettings::restore(&backup) {
+            Ok(restored) => {
+                settings.set(restored);
+                recovery.set(None);
+            }
+            Err(e) => {
+                if let Some(recovery) = recovery.write().as_mut() {
+                    recovery.error = format!("Cannot restore {backup:?}: {e}");
+                    recovery.backup = None;
+                }
+            }
+        }
+    };
+
+    rsx! {
+        rect {
+            width: "fill",
+            padding: "12",
+            spacing: "8",
+            corner_radius: "8",
+            background: "rgb(120, 30, 30, 0.9)",
+
+            label {
+                font_size: "16",
+                "Your settings couldn't be loaded, the defaults are used for now."
+            }
+            label {
+                font_size: "12",
+                "{current.error}"
+            }
+            label {
+                font_size: "12",
+                "{kept}"
+            }
+            rect {
+                direction: "horizontal",
+                spacing: "8",
+                if let Some(name) = backup_name {
+                    MyButton {
+                        onpress: onrestore,
+                        rect {
+                            padding: "4 16",
+                            label { "Restore {name}" }
+                        }
+                    }
+                }
+                MyButton {
+                    onpress: move |_| {
+                        block_saving(false);
+                        settings.peek().save();
+                        recovery.set(None);
+                    },
+                    rect {
+                        padding: "4 16",
+                        label { "Keep the defaults" }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,53 @@
+use freya::prelude::*;
+
+use crate::utils::download::{DownloadManager, DownloadState, format_bytes};
+
+/// Number of running downloads and their combined speed, a plain arrow when idle.
+#[component]
+pub fn MyDownloadIndicator() -> Element {
+    let manager = use_context::<DownloadManager>();
+    let tasks = manager.tasks.read();
+
+    let (mut running, mut queued, mut speed) = (0, 0, 0);
+    for task in tasks.iter() {
+        let progress = task.progress.read();
+        match progress.state {
+            DownloadState::Downloading => {
+                running += 1;
+                speed += progress.speed;
+            }
+            DownloadState::Queued => queued += 1,
+            _ => {}
+        }
+    }
+
+    if running == 0 && queued == 0 {
+        return rsx!(
+            label {
+                font_size: "24",
+                "↓"
+            }
+        );
+    }
+
+    rsx!(
+        rect {
+            cross_align: "center",
+            label {
+                font_size: "12",
+                "↓ {running}"
+            }
+            label {
+                font_size: "10",
+                "{format_bytes(speed)}/s"
+            }
+            if queued > 0 {
+                label {
+                    font_size: "10",
+                    color: "rgb(160, 160, 160)",
+                    "+{queued} queued"
+                }
+            }
+        }
+    )
+}
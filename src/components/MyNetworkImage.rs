@@ -1,9 +1,11 @@
+use std::fs;
+
 use bytes::Bytes;
 use freya::prelude::*;
 use libwebp::WebPDecodeRGBA;
 use reqwest::{Url, header::CONTENT_TYPE};
 
-use crate::settings::GlobalSettings;
+use crate::settings::{Endpoints, GlobalSettings};
 
 #[derive(Props, Clone, PartialEq)]
 pub struct MyNetworkImageProps {
@@ -56,16 +58,20 @@ pub fn MyNetworkImage(
     let ctx = &dioxus::hooks::use_context::<Signal<GlobalSettings>>();
     let settings = &ctx.read();
     let cache_path = &settings.cache_directory;
+    let image_host = settings.endpoints.images();
 
     let a11y_id = focus.attribute();
     let key = url.to_string();
     let url = url.read();
 
-    if let Ok(asset) = cacache::read_sync(cache_path, &key) {
+    // Local files are read directly so edits show up without touching the cache
+    let is_local = url.scheme() == "file";
+
+    if !is_local && let Ok(asset) = cacache::read_sync(cache_path, &key) {
         // Image loaded from cache
         status.set(ImageState::Loaded(asset.into()));
     } else {
-        to_owned![url, cache_path];
+        to_owned![url, cache_path, image_host];
         use_effect(move || {
             // Cancel previous asset fetching requests
             for asset_task in assets_tasks.write().drain(..) {
@@ -73,11 +79,20 @@ pub fn MyNetworkImage(
             }
 
             // Loading image
-            to_owned![key, url, cache_path];
+            to_owned![key, url, cache_path, image_host];
             let asset_task = spawn(async move {
+                let url = if is_local {
+                    url
+                } else {
+                    Endpoints::rewrite(url.as_str(), image_host)
+                        .parse()
+                        .unwrap_or(url)
+                };
                 let asset = fetch_image(url).await;
                 if let Ok(asset_bytes) = asset {
-                    let _ = cacache::write_sync(cache_path, &key, &asset_bytes);
+                    if !is_local {
+                        let _ = cacache::write_sync(cache_path, &key, &asset_bytes);
+                    }
 
                     // Image loaded
                     status.set(ImageState::Loaded(asset_bytes));
@@ -153,6 +168,22 @@ pub fn MyNetworkImage(
 
 #[allow(dead_code)]
 async fn fetch_image(url: Url) -> Result<Bytes, String> {
+    if url.scheme() == "file" {
+        let path = url
+            .to_file_path()
+            .map_err(|_| format!("Invalid image path: {url}"))?;
+        let bytes = fs::read(&path).map_err(|e| format!("Failed to read image {path:?}: {e}"))?;
+        let is_webp = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("webp"));
+
+        return if is_webp {
+            decode_webp(&bytes)
+        } else {
+            Ok(bytes.into())
+        };
+    }
+
     let res = reqwest::get(url.clone())
         .await
         .map_err(|e| format!("Failed to fetch image: {e}"))?;
@@ -170,25 +201,27 @@ async fn fetch_image(url: Url) -> Result<Bytes, String> {
         .map_err(|e| format!("Failed to fetch image: {e}"))?;
 
     match content_type.as_str() {
-        "image/webp" => {
-            let (width, height, buf) =
-                WebPDecodeRGBA(&bytes).map_err(|e| format!("Failed to decode WebP image: {e}"))?;
-
-            let encoded_bytes = lodepng::encode_memory(
-                &buf,
-                width as usize,
-                height as usize,
-                lodepng::ColorType::RGBA,
-                8,
-            )
-            .map_err(|e| format!("Failed to encode PNG image: {e}"))?;
-
-            Ok(encoded_bytes.into())
-        }
+        "image/webp" => decode_webp(&bytes),
         _ => Ok(bytes),
     }
 }
 
+fn decode_webp(bytes: &[u8]) -> Result<Bytes, String> {
+    let (width, height, buf) =
+        WebPDecodeRGBA(bytes).map_err(|e| format!("Failed to decode WebP image: {e}"))?;
+
+    let encoded_bytes = lodepng::encode_memory(
+        &buf,
+        width as usize,
+        height as usize,
+        lodepng::ColorType::RGBA,
+        8,
+    )
+    .map_err(|e| format!("Failed to encode PNG image: {e}"))?;
+
+    Ok(encoded_bytes.into())
+}
+
 enum ImageState {
     Loading,
     Loaded(Bytes),
@@ -3,19 +3,19 @@ use reqwest::Url;
 
 use crate::{
     components::{MyAnimatedCarousel, MyNetworkImage},
-    context::Context,
-    game_providers::hoyoplay::get_game_content,
+    context::NewsStore,
 };
 
 #[component]
 pub fn MyNewsWidget(game_id: String) -> Element {
-    let ctx = &use_context::<Context>();
-    let Some(content) = ctx.api_news.get(&game_id) else {
+    let news = use_context::<NewsStore>();
+    let mut selected = use_signal(|| 0);
+
+    let news = news.read();
+    let Some(Ok(content)) = news.get(&game_id) else {
         return rsx! {};
     };
 
-    let mut selected = use_signal(|| 0);
-
     let len = content.banners.len();
     let onwheel = move |e: Event<WheelData>| {
         let current = selected();
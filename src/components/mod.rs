@@ -4,6 +4,8 @@
 mod Expand;
 mod MyAnimatedCarousel;
 mod MyButton;
+mod MyConfigRecovery;
+mod MyDownloadIndicator;
 mod MyNetworkImage;
 mod MyNewsWidget;
 mod MySidebarItem;
@@ -11,6 +13,8 @@ mod MySidebarItem;
 pub use Expand::*;
 pub use MyAnimatedCarousel::*;
 pub use MyButton::*;
+pub use MyConfigRecovery::*;
+pub use MyDownloadIndicator::*;
 pub use MyNetworkImage::*;
 pub use MyNewsWidget::*;
 pub use MySidebarItem::*;
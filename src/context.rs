@@ -1,10 +1,148 @@
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
 
-use crate::game_providers::hoyoplay::api::{Content, Game};
+use freya::prelude::{Signal, Writable};
+use reqwest::header::HeaderMap;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    game_providers::{
+        hoyoplay::api::{ApiError, Content, Game},
+        kuro,
+    },
+    settings::GlobalSettings,
+    utils::cache::{CacheEntry, read_entry, write_entry},
+};
 
 #[allow(dead_code)]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct Context {
     pub api_games: Vec<Game>,
-    pub api_news: HashMap<String, Content>,
+    /// Why the game list couldn't be loaded
+    pub api_games_error: Option<ApiError>,
+    pub kuro_games: Vec<kuro::Game>,
+    /// Installed games missing from the lists, as they were last listed
+    pub offline_games: HashMap<String, GameSummary>,
+}
+
+/// Name and artwork of a listed game, cached so installed games keep them
+/// while the api can't be reached. The images themselves are cached by
+/// `MyNetworkImage` under their url.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GameSummary {
+    pub name: String,
+    pub icon: Option<String>,
+    pub background: Option<String>,
+}
+
+impl GameSummary {
+    pub fn read(cache_path: &Path, game_id: &str) -> Option<GameSummary> {
+        read_entry(cache_path, &Self::key(game_id)).map(|entry| entry.data)
+    }
+
+    pub fn write(&self, cache_path: &Path, game_id: &str) {
+        let entry = CacheEntry::new(self.clone(), &HeaderMap::new());
+        write_entry(cache_path, &Self::key(game_id), &entry);
+    }
+
+    fn key(game_id: &str) -> String {
+        format!("elysia:game-summary:{game_id}")
+    }
+}
+
+/// Game content by game id, filled in the background and whenever a game page
+/// opens, so nothing has to wait for every game to load.
+pub type NewsStore = Signal<HashMap<String, Result<Content, ApiError>>>;
+
+impl Context {
+    /// Ids of every game in sidebar order.
+    pub fn game_ids(&self) -> Vec<String> {
+        self.api_games
+            .iter()
+            .filter(|game| game.display_status.is_visible())
+            .map(|game| game.id.clone())
+            .chain(self.kuro_games.iter().map(|game| game.id.clone()))
+            .collect()
+    }
+
+    /// Caches the summary of every listed game, and reads the cached ones of
+    /// the installed games that aren't listed.
+    pub fn sync_summaries(&mut self, settings: &GlobalSettings) {
+        let cache_path = &settings.cache_directory;
+
+        let hoyoplay = self.api_games.iter().map(|game| {
+            let summary = GameSummary {
+                name: game.display.name.clone(),
+                icon: Some(game.display.icon.url.clone()),
+                background: Some(game.display.background.url.clone()),
+            };
+            (&game.id, summary)
+        });
+        let kuro = self.kuro_games.iter().map(|game| {
+            let background = game
+                .background
+                .as_ref()
+                .map(|background| background.background_file.clone());
+            let summary = GameSummary {
+                name: game.name.clone(),
+                icon: background.clone(),
+                background,
+            };
+            (&game.id, summary)
+        });
+        for (id, summary) in hoyoplay.chain(kuro) {
+            if GameSummary::read(cache_path, id).as_ref() != Some(&summary) {
+                summary.write(cache_path, id);
+            }
+        }
+
+        let known = self.game_ids();
+        self.offline_games = settings
+            .installed_games
+            .keys()
+            .filter(|id| !known.contains(id) && !settings.custom_games.contains_key(*id))
+            .filter_map(|id| Some((id.clone(), GameSummary::read(cache_path, id)?)))
+            .collect();
+    }
+}
+
+/// Bumped whenever cached api data changes, so the context gets rebuilt.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CacheRefresh {
+    pub generation: u64,
+    /// Set by the "Refresh" action to skip the cache entirely
+    pub bypass: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Connectivity {
+    #[default]
+    Online,
+    /// The api couldn't be reached, the library is built from the cache and
+    /// installed games until a connectivity check succeeds
+    Offline,
+}
+
+/// Switches to offline mode after a request got no response at all. Outside
+/// of the launcher, like in the integration tests, there's nothing to switch.
+pub fn set_offline() {
+    if let Some(mut connectivity) = dioxus::prelude::try_consume_context::<Signal<Connectivity>>() {
+        connectivity.set(Connectivity::Offline);
+    }
+}
+
+/// Set when the config couldn't be loaded, until a backup is restored or the
+/// defaults are kept.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigRecovery {
+    pub error: String,
+    /// Where the broken config was moved
+    pub corrupted: Option<PathBuf>,
+    /// Newest config that loaded fine
+    pub backup: Option<PathBuf>,
+    /// The config was written by a newer version, it's kept as it is and
+    /// nothing is saved this session
+    pub newer_version: bool,
 }
@@ -1,4 +1,9 @@
-use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ApiResponse<DataType> {
@@ -7,6 +12,46 @@ pub struct ApiResponse<DataType> {
     pub data: DataType,
 }
 
+impl ApiResponse<serde_json::Value> {
+    /// Turns a non-zero retcode into an error before `data` is parsed, since
+    /// error responses usually come with `data: null`.
+    pub fn into_data<DataType>(self) -> Result<DataType, ApiError>
+    where
+        DataType: DeserializeOwned,
+    {
+        if self.retcode != 0 {
+            return Err(ApiError::Server {
+                retcode: self.retcode,
+                message: self.message,
+            });
+        }
+
+        serde_json::from_value(self.data).map_err(|e| ApiError::Parse(e.to_string()))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ApiError {
+    /// The request never got a response, or got an error status
+    Request(String),
+    /// The response isn't what we expected
+    Parse(String),
+    /// The server answered with a non-zero retcode, e.g. maintenance or region lock
+    Server { retcode: i32, message: String },
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiError::Request(e) => write!(f, "Api request error: {e}"),
+            ApiError::Parse(e) => write!(f, "Api parse error: {e}"),
+            ApiError::Server { retcode, message } => {
+                write!(f, "Server returned an error ({retcode}): {message}")
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GetGames {
     pub games: Vec<Game>,
@@ -17,17 +62,62 @@ pub struct GetGameContent {
     pub content: Content,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetGamePackages {
+    pub game_packages: Vec<GamePackages>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetGameChannelSdks {
+    pub game_channel_sdks: Vec<ChannelSdk>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetGamePlugins {
+    pub plugin_releases: Vec<PluginRelease>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Game {
     pub id: String,
     pub biz: String,
     pub display: Display,
-    pub reservation: Option<serde_json::Value>, // null or future object
-    pub display_status: String,
+    pub reservation: Option<Reservation>,
+    pub display_status: DisplayStatus,
     #[serde(default)]
     pub game_server_configs: Vec<GameServerConfig>,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DisplayStatus {
+    #[serde(rename = "LAUNCHER_GAME_DISPLAY_STATUS_AVAILABLE")]
+    Available,
+    #[serde(rename = "LAUNCHER_GAME_DISPLAY_STATUS_RESERVATION_ENABLED")]
+    ReservationEnabled,
+    #[serde(rename = "LAUNCHER_GAME_DISPLAY_STATUS_COMING_SOON")]
+    ComingSoon,
+    /// Any status we don't know about, treated as not meant to be shown.
+    #[serde(other)]
+    Hidden,
+}
+
+impl DisplayStatus {
+    pub fn is_visible(&self) -> bool {
+        *self != DisplayStatus::Hidden
+    }
+
+    pub fn is_released(&self) -> bool {
+        *self == DisplayStatus::Available
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Reservation {
+    pub link: String,
+    #[serde(default)]
+    pub login_state_in_link: bool,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Display {
     pub language: String,
@@ -68,8 +158,93 @@ pub struct GameServerConfig {
     pub auto_scan_registry_key: String,
     pub package_detection_info: String,
     pub game_id: String,
-    pub reservation: Option<serde_json::Value>,
-    pub display_status: String,
+    pub reservation: Option<Reservation>,
+    pub display_status: DisplayStatus,
+}
+
+impl GameServerConfig {
+    /// Whether an existing installation was downloaded from this server.
+    pub fn matches(&self, install_path: &Path) -> bool {
+        if !self.package_detection_info.is_empty()
+            && install_path.join(&self.package_detection_info).exists()
+        {
+            return true;
+        }
+
+        !self.package_name.is_empty() && install_path.join(self.executable()).is_file()
+    }
+
+    pub fn executable(&self) -> PathBuf {
+        PathBuf::from(format!("{}.exe", self.package_name))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GamePackages {
+    pub game: GameInfo,
+    pub main: PackageSet,
+    pub pre_download: PackageSet,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackageSet {
+    pub major: Option<PackageRelease>, // null when there's nothing to pre-download
+    #[serde(default)]
+    pub patches: Vec<PackageRelease>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackageRelease {
+    pub version: String,
+    pub game_pkgs: Vec<Package>,
+    #[serde(default)]
+    pub audio_pkgs: Vec<AudioPackage>,
+    #[serde(default)]
+    pub res_list_url: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Package {
+    pub url: String,
+    pub md5: String,
+    pub size: String, // sizes are sent as strings
+    pub decompressed_size: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AudioPackage {
+    pub language: String,
+    pub url: String,
+    pub md5: String,
+    pub size: String,
+    pub decompressed_size: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChannelSdk {
+    pub game: GameInfo,
+    pub version: String,
+    pub channel_sdk_pkg: Package,
+    #[serde(default)]
+    pub pkg_version_file_name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PluginRelease {
+    pub game: GameInfo,
+    pub plugins: Vec<Plugin>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Plugin {
+    pub plugin_id: String,
+    pub release_id: String,
+    pub version: String,
+    pub plugin_pkg: Package,
+    #[serde(default)]
+    pub post_exec: String, // e.g. running DXSETUP after unpacking
+    #[serde(default)]
+    pub pkg_version_file_name: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
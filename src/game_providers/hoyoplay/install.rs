@@ -0,0 +1,189 @@
+use std::{
+    collections::HashMap,
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use super::api::{ChannelSdk, Package, PackageRelease, Plugin};
+use crate::{
+    settings::{Endpoints, InstalledGame},
+    utils::{
+        download::{download_file, md5_matches},
+        extract::Extraction,
+        filesystem::ensure_dir,
+    },
+};
+
+/// Downloads, checks and unpacks every volume of a release's game package.
+// TODO: apply diff patches instead of downloading the whole game on updates
+// TODO: voice packs
+pub async fn install_release(
+    release: &PackageRelease,
+    install_path: &Path,
+    temp_dir: &Path,
+    endpoints: &Endpoints,
+    extraction: &Extraction,
+) -> Result<(), String> {
+    ensure_dir(install_path)?;
+    ensure_dir(temp_dir)?;
+
+    let mut volumes = Vec::new();
+    for package in &release.game_pkgs {
+        volumes.push(download_package(package, temp_dir, endpoints).await?);
+    }
+
+    extraction.run(&volumes, install_path).await?;
+
+    for volume in volumes {
+        let _ = fs::remove_file(volume);
+    }
+
+    Ok(())
+}
+
+/// Key of the channel SDK in `InstalledGame::extras`, removed when the channel
+/// changes so the SDK of the new one gets installed.
+pub const CHANNEL_SDK_ID: &str = "channel_sdk";
+
+/// A channel SDK or plugin, installed on top of the game package.
+pub struct Extra {
+    pub id: String,
+    pub version: String,
+    pub package: Package,
+    /// File the package writes into the game directory, its absence means the
+    /// extra has to be installed again
+    pub version_file: String,
+    /// Command to run from the game directory once unpacked, e.g. an installer
+    pub post_exec: String,
+}
+
+impl From<ChannelSdk> for Extra {
+    fn from(sdk: ChannelSdk) -> Self {
+        Self {
+            id: CHANNEL_SDK_ID.to_string(),
+            version: sdk.version,
+            package: sdk.channel_sdk_pkg,
+            version_file: sdk.pkg_version_file_name,
+            post_exec: String::new(),
+        }
+    }
+}
+
+impl From<Plugin> for Extra {
+    fn from(plugin: Plugin) -> Self {
+        Self {
+            id: plugin.plugin_id,
+            version: plugin.version,
+            package: plugin.plugin_pkg,
+            version_file: plugin.pkg_version_file_name,
+            post_exec: plugin.post_exec,
+        }
+    }
+}
+
+/// Installs extras that are missing, outdated or lost their version file, and
+/// records their versions in `versions`.
+pub async fn update_extras(
+    extras: Vec<Extra>,
+    versions: &mut HashMap<String, String>,
+    game: &InstalledGame,
+    temp_dir: &Path,
+    endpoints: &Endpoints,
+    extraction: &Extraction,
+) -> Result<(), String> {
+    ensure_dir(temp_dir)?;
+    let install_path = &game.install_path;
+
+    for extra in extras {
+        let intact =
+            extra.version_file.is_empty() || install_path.join(&extra.version_file).exists();
+        if intact && versions.get(&extra.id) == Some(&extra.version) {
+            continue;
+        }
+
+        println!("Installing {} {}..", extra.id, extra.version);
+        let archive = download_package(&extra.package, temp_dir, endpoints).await?;
+        extraction
+            .run(std::slice::from_ref(&archive), install_path)
+            .await?;
+        let _ = fs::remove_file(archive);
+        run_post_exec(game, &extra.post_exec).await?;
+
+        versions.insert(extra.id, extra.version);
+    }
+
+    Ok(())
+}
+
+/// Runs the command an extra asks for after unpacking, like a redistributable
+/// installer, through the game's runner and from its directory.
+async fn run_post_exec(game: &InstalledGame, post_exec: &str) -> Result<(), String> {
+    let mut words = post_exec.split_whitespace();
+    let Some(program) = words.next() else {
+        return Ok(());
+    };
+    let arguments = words.map(OsString::from).collect::<Vec<_>>();
+    // Written with Windows separators, relative to the game directory
+    let program = game.install_path.join(program.replace('\\', "/"));
+
+    println!("Running {post_exec}..");
+    let command = game.runner.command(game, &program, &arguments)?;
+    let status = tokio::process::Command::from(command)
+        .status()
+        .await
+        .map_err(|e| format!("Cannot run {post_exec}: {e}"))?;
+    if !status.success() {
+        return Err(format!("{post_exec} failed with {status}"));
+    }
+
+    Ok(())
+}
+
+/// Downloads a package into `temp_dir` and checks it, reusing an intact file
+/// left over from an interrupted install.
+async fn download_package(
+    package: &Package,
+    temp_dir: &Path,
+    endpoints: &Endpoints,
+) -> Result<PathBuf, String> {
+    let file_name = package
+        .url
+        .rsplit('/')
+        .next()
+        .filter(|name| !name.is_empty())
+        .ok_or_else(|| format!("Invalid package url: {}", package.url))?;
+    let path = temp_dir.join(file_name);
+
+    if md5_matches(&path, &package.md5)? {
+        return Ok(path);
+    }
+
+    println!("Downloading {file_name}..");
+    let url = Endpoints::rewrite(&package.url, endpoints.hoyoplay_cdn());
+    download_file(&url, &path).await?;
+
+    if !md5_matches(&path, &package.md5)? {
+        let _ = fs::remove_file(&path);
+        return Err(format!("Checksum mismatch for {file_name}"));
+    }
+
+    Ok(path)
+}
+
+/// First `.exe` in the root of an installation, for games without a server
+/// config telling us the executable name.
+pub fn find_executable(install_path: &Path) -> Option<PathBuf> {
+    let mut executables = fs::read_dir(install_path)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| PathBuf::from(entry.file_name()))
+        .filter(|name| {
+            name.extension()
+                .is_some_and(|extension| extension.eq_ignore_ascii_case("exe"))
+        })
+        .collect::<Vec<_>>();
+    executables.sort();
+
+    executables.into_iter().next()
+}
@@ -1,13 +1,29 @@
 pub mod api;
+pub mod install;
 
-use std::path::PathBuf;
+use std::{
+    cell::Cell,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
-use api::{ApiResponse, Game, GetGameContent, GetGames};
-use freya::prelude::{Readable, Signal};
-use reqwest;
+use api::{
+    ApiError, ApiResponse, ChannelSdk, GamePackages, GetGameChannelSdks, GetGameContent,
+    GetGamePackages, GetGamePlugins, GetGames, Plugin,
+};
+use freya::prelude::{Readable, Signal, Writable, spawn_forever};
+use reqwest::{
+    self, StatusCode,
+    header::{IF_MODIFIED_SINCE, IF_NONE_MATCH},
+};
 use serde::{Deserialize, Serialize};
 
-use crate::settings::GlobalSettings;
+use crate::{
+    context::{CacheRefresh, Connectivity, set_offline},
+    globals::paths,
+    settings::{Channel, Endpoints, GlobalSettings},
+    utils::cache::{CacheEntry, CachePolicy, read_entry, write_entry},
+};
 
 // FIXME: add all query params that should be there
 // TODO: language selection
@@ -15,49 +31,260 @@ const API_URL: &str = "https://sg-hyp-api.hoyoverse.com/hyp/hyp-connect/api";
 // TODO: other launchers (global, china, 3x bilibili?)
 const LAUNCHER_ID: &str = "VYTpXlbWo8"; // Global
 
-pub async fn get_games() -> Result<GetGames, String> {
-    let url = format!("{API_URL}/getGames?launcher_id={LAUNCHER_ID}&language=en-us");
+// Banners and posts change more often than the game list
+const GAMES_TTL: Duration = Duration::from_secs(6 * 60 * 60);
+const GAME_CONTENT_TTL: Duration = Duration::from_secs(30 * 60);
+const GAME_PACKAGES_TTL: Duration = Duration::from_secs(30 * 60);
 
-    return cached_request(&url).await;
+thread_local! {
+    /// Revalidations still running and whether any of them got new data, so
+    /// the context is rebuilt once after the last one instead of once per entry
+    static REVALIDATIONS: Cell<(usize, bool)> = const { Cell::new((0, false)) };
 }
 
-pub async fn get_game_content(game_id: &str) -> Result<GetGameContent, String> {
+fn api_url() -> String {
+    endpoints()
+        .hoyoplay_api()
+        .unwrap_or_else(|| API_URL.to_string())
+}
+
+/// Endpoints of the running launcher, outside of it (like in the integration
+/// tests) only the environment variables apply.
+fn endpoints() -> Endpoints {
+    dioxus::prelude::try_consume_context::<Signal<GlobalSettings>>()
+        .map(|ctx| ctx.read().endpoints.clone())
+        .unwrap_or_default()
+}
+
+/// Cache directory of the running launcher, or the default one.
+fn cache_directory() -> Result<PathBuf, String> {
+    match dioxus::prelude::try_consume_context::<Signal<GlobalSettings>>() {
+        Some(ctx) => Ok(ctx.read().cache_directory.clone()),
+        None => paths().map(|paths| paths.cache),
+    }
+}
+
+/// Whether the api answers at all, used to leave offline mode.
+pub async fn check_connectivity() -> bool {
+    let client = reqwest::Client::new();
+
+    client
+        .get(format!(
+            "{}/getGames?launcher_id={LAUNCHER_ID}&language=en-us",
+            api_url()
+        ))
+        .timeout(Duration::from_secs(5))
+        .send()
+        .await
+        .is_ok_and(|response| response.status().is_success())
+}
+
+pub async fn get_games(policy: CachePolicy) -> Result<GetGames, ApiError> {
+    let url = format!(
+        "{}/getGames?launcher_id={LAUNCHER_ID}&language=en-us",
+        api_url()
+    );
+
+    return cached_request(&url, GAMES_TTL, policy).await;
+}
+
+pub async fn get_game_content(
+    game_id: &str,
+    policy: CachePolicy,
+) -> Result<GetGameContent, ApiError> {
+    let url = format!(
+        "{}/getGameContent?game_id={}&launcher_id={LAUNCHER_ID}&language=en-us",
+        api_url(),
+        game_id
+    );
+
+    return cached_request(&url, GAME_CONTENT_TTL, policy).await;
+}
+
+/// Packages of a game, `game_id` being the id of the selected server config.
+pub async fn get_game_packages(
+    game_id: &str,
+    policy: CachePolicy,
+) -> Result<GamePackages, ApiError> {
+    let url = format!(
+        "{}/getGamePackages?game_ids[]={}&launcher_id={LAUNCHER_ID}",
+        api_url(),
+        game_id
+    );
+
+    let response: GetGamePackages = cached_request(&url, GAME_PACKAGES_TTL, policy).await?;
+
+    response
+        .game_packages
+        .into_iter()
+        .find(|packages| packages.game.id == game_id)
+        .ok_or_else(|| ApiError::Parse(format!("No packages for game {game_id}")))
+}
+
+/// Channel SDK the game needs for `channel`, if any.
+pub async fn get_channel_sdk(
+    game_id: &str,
+    channel: Channel,
+    policy: CachePolicy,
+) -> Result<Option<ChannelSdk>, ApiError> {
+    let (channel, sub_channel) = match channel {
+        Channel::Official => (1, 0),
+        Channel::Bilibili => (14, 0),
+    };
     let url = format!(
-        "{API_URL}/getGameContent?game_id={}&launcher_id={LAUNCHER_ID}&language=en-us",
+        "{}/getGameChannelSDKs?channel={channel}&sub_channel={sub_channel}&game_ids[]={}&launcher_id={LAUNCHER_ID}",
+        api_url(),
         game_id
     );
 
-    return cached_request(&url).await;
+    let response: GetGameChannelSdks = cached_request(&url, GAME_PACKAGES_TTL, policy).await?;
+
+    Ok(response
+        .game_channel_sdks
+        .into_iter()
+        .find(|sdk| sdk.game.id == game_id))
 }
 
-// TODO: cache invalidation on demand
-async fn cached_request<Type>(url: &str) -> Result<Type, String>
+pub async fn get_game_plugins(game_id: &str, policy: CachePolicy) -> Result<Vec<Plugin>, ApiError> {
+    let url = format!(
+        "{}/getGamePlugins?game_ids[]={}&launcher_id={LAUNCHER_ID}",
+        api_url(),
+        game_id
+    );
+
+    let response: GetGamePlugins = cached_request(&url, GAME_PACKAGES_TTL, policy).await?;
+
+    Ok(response
+        .plugin_releases
+        .into_iter()
+        .find(|release| release.game.id == game_id)
+        .map(|release| release.plugins)
+        .unwrap_or_default())
+}
+
+async fn cached_request<Type>(
+    url: &str,
+    ttl: Duration,
+    policy: CachePolicy,
+) -> Result<Type, ApiError>
 where
-    Type: for<'a> Deserialize<'a> + Serialize,
+    Type: for<'a> Deserialize<'a> + Serialize + Clone + 'static,
 {
-    let ctx = &dioxus::hooks::use_context::<Signal<GlobalSettings>>();
-    let settings = &ctx.read();
-    let cache_path = &settings.cache_directory;
-
-    if let Ok(asset) = cacache::read_sync(cache_path, url) {
-        let parsed = serde_json::from_slice(&asset).map_err(|e| format!("Cache parse error: {e}"));
-        if let Ok(games) = parsed {
-            return Ok(games);
+    let cache_path = cache_directory().map_err(ApiError::Request)?;
+
+    if policy == CachePolicy::PreferCache
+        && let Some(entry) = read_entry::<Type>(&cache_path, url)
+    {
+        if !entry.is_fresh(ttl)
+            && let Some(mut refresh) =
+                dioxus::prelude::try_consume_context::<Signal<CacheRefresh>>()
+            && let Some(mut connectivity) =
+                dioxus::prelude::try_consume_context::<Signal<Connectivity>>()
+        {
+            // Show stale data now, the context is rebuilt once the new data arrives
+            let url = url.to_string();
+            let stale = entry.clone();
+            let (running, changed) = REVALIDATIONS.get();
+            REVALIDATIONS.set((running + 1, changed));
+            // Not tied to the page that asked, leaving it mustn't lose the count
+            spawn_forever(async move {
+                let changed = match fetch_entry(&url, &cache_path, Some(stale)).await {
+                    Ok((_, changed)) => changed,
+                    Err(e) => {
+                        println!("Failed to revalidate {url}: {e}");
+                        if let ApiError::Request(_) = e {
+                            connectivity.set(Connectivity::Offline);
+                        }
+                        false
+                    }
+                };
+
+                let (running, any_changed) = REVALIDATIONS.get();
+                let any_changed = any_changed || changed;
+                if running > 1 {
+                    REVALIDATIONS.set((running - 1, any_changed));
+                    return;
+                }
+
+                REVALIDATIONS.set((0, false));
+                if any_changed {
+                    let generation = refresh.peek().generation;
+                    refresh.set(CacheRefresh {
+                        generation: generation + 1,
+                        bypass: false,
+                    });
+                }
+            });
         }
+
+        return Ok(entry.data);
     }
 
+    match fetch_entry(url, &cache_path, None).await {
+        Ok((entry, _)) => Ok(entry.data),
+        // A forced refresh while offline still has something to show
+        Err(ApiError::Request(e)) => {
+            set_offline();
+            match read_entry::<Type>(&cache_path, url) {
+                Some(entry) => {
+                    println!("Failed to refresh {url}, using cached data: {e}");
+                    Ok(entry.data)
+                }
+                None => Err(ApiError::Request(e)),
+            }
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Fetches and caches a response, sending the validators of `previous` so an
+/// unchanged response doesn't have to be downloaded again. The returned flag is
+/// false when the server answered with 304 Not Modified.
+async fn fetch_entry<Type>(
+    url: &str,
+    cache_path: &Path,
+    previous: Option<CacheEntry<Type>>,
+) -> Result<(CacheEntry<Type>, bool), ApiError>
+where
+    Type: for<'a> Deserialize<'a> + Serialize,
+{
     let client = reqwest::Client::new();
-    let response = client
-        .get(url)
+    let mut request = client.get(url);
+    if let Some(previous) = &previous {
+        if let Some(etag) = &previous.etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &previous.last_modified {
+            request = request.header(IF_MODIFIED_SINCE, last_modified);
+        }
+    }
+
+    let response = request
         .send()
         .await
-        .map_err(|e| format!("Api request error: {e}"))?;
-    let response: ApiResponse<Type> = response
+        .map_err(|e| ApiError::Request(e.to_string()))?;
+
+    if response.status() == StatusCode::NOT_MODIFIED
+        && let Some(mut previous) = previous
+    {
+        previous.touch();
+        write_entry(cache_path, url, &previous);
+        return Ok((previous, false));
+    }
+
+    // An error page would only show up as a confusing parse error
+    let response = response
+        .error_for_status()
+        .map_err(|e| ApiError::Request(e.to_string()))?;
+    let headers = response.headers().clone();
+    let response: ApiResponse<serde_json::Value> = response
         .json()
         .await
-        .map_err(|e| format!("Api parse error: {e}"))?;
+        .map_err(|e| ApiError::Parse(e.to_string()))?;
 
-    let _ = cacache::write_sync(cache_path, url, serde_json::to_vec(&response.data).unwrap());
+    // Errors are returned before anything is written, so they never end up cached
+    let entry = CacheEntry::new(response.into_data()?, &headers);
+    write_entry(cache_path, url, &entry);
 
-    Ok(response.data)
+    Ok((entry, true))
 }
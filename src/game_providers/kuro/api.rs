@@ -0,0 +1,56 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GameIndex {
+    pub default: GameRelease,
+    pub predownload: Option<GameRelease>, // only present before a new version
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GameRelease {
+    pub cdn_list: Vec<Cdn>,
+    pub version: String,
+    pub resources: String,
+    pub resources_base_path: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Cdn {
+    #[serde(rename = "P")]
+    pub priority: i32,
+    pub url: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResourceList {
+    pub resource: Vec<Resource>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Resource {
+    pub dest: String,
+    pub md5: String,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LauncherIndex {
+    pub function_code: FunctionCode,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FunctionCode {
+    pub background: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Background {
+    pub background_file: String,
+    #[serde(default)]
+    pub first_frame_image: String,
+    #[serde(default)]
+    pub slogan: String,
+}
@@ -0,0 +1,336 @@
+pub mod api;
+
+use std::{
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+use api::{Background, GameIndex, GameRelease, LauncherIndex, Resource, ResourceList};
+use freya::prelude::{Readable, Signal};
+use futures::future::{join, join_all};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    context::set_offline,
+    globals::paths,
+    settings::{Endpoints, GlobalSettings},
+    utils::download::{download_file, md5_matches},
+};
+
+const API_URL: &str = "https://prod-alicdn-gamestarter.kurogame.com";
+
+pub struct KnownGame {
+    pub id: &'static str,
+    pub biz_name: &'static str,
+    pub name: &'static str,
+    pub launcher_key: &'static str,
+    pub executable: &'static str,
+}
+
+// Kuro has no endpoint listing games, every launcher is built for a single one
+// TODO: china and other regions
+pub const KNOWN_GAMES: &[KnownGame] = &[KnownGame {
+    id: "G153",
+    biz_name: "wuwa_global",
+    name: "Wuthering Waves",
+    launcher_key: "50004_obOHXFrFanqsaIEOmuKroCcbZkQRBC7c",
+    executable: "Wuthering Waves.exe",
+}];
+
+#[derive(Debug, Clone)]
+pub struct Game {
+    pub id: String,
+    pub biz_name: String,
+    pub name: String,
+    pub executable_path: PathBuf,
+    pub index: GameIndex,
+    pub background: Option<Background>,
+}
+
+fn api_url() -> String {
+    endpoints().kuro().unwrap_or_else(|| API_URL.to_string())
+}
+
+/// Endpoints of the running launcher, outside of it only the environment
+/// variables apply.
+fn endpoints() -> Endpoints {
+    dioxus::prelude::try_consume_context::<Signal<GlobalSettings>>()
+        .map(|ctx| ctx.read().endpoints.clone())
+        .unwrap_or_default()
+}
+
+/// Cache directory of the running launcher, or the default one.
+fn cache_directory() -> Result<PathBuf, String> {
+    match dioxus::prelude::try_consume_context::<Signal<GlobalSettings>>() {
+        Some(ctx) => Ok(ctx.read().cache_directory.clone()),
+        None => paths().map(|paths| paths.cache),
+    }
+}
+
+fn join_url(base: &str, path: &str) -> String {
+    format!(
+        "{}/{}",
+        base.trim_end_matches('/'),
+        path.trim_start_matches('/')
+    )
+}
+
+pub async fn get_games() -> Vec<Game> {
+    let results = join_all(KNOWN_GAMES.iter().map(get_game)).await;
+
+    KNOWN_GAMES
+        .iter()
+        .zip(results)
+        .filter_map(|(known, result)| {
+            result
+                .map_err(|e| println!("Failed to load {} from api: {e}", known.name))
+                .ok()
+        })
+        .collect()
+}
+
+async fn get_game(known: &KnownGame) -> Result<Game, String> {
+    let url = join_url(
+        &api_url(),
+        &format!(
+            "launcher/game/{}/{}/index.json",
+            known.id, known.launcher_key
+        ),
+    );
+    let (index, background) = join(cached_request::<GameIndex>(&url), get_background(known)).await;
+    let index = index?;
+
+    let background = background
+        .map_err(|e| println!("Failed to load background for {}: {e}", known.name))
+        .ok();
+
+    Ok(Game {
+        id: known.id.to_string(),
+        biz_name: known.biz_name.to_string(),
+        name: known.name.to_string(),
+        executable_path: PathBuf::from(known.executable),
+        index,
+        background,
+    })
+}
+
+async fn get_background(known: &KnownGame) -> Result<Background, String> {
+    let launcher_url = join_url(
+        &api_url(),
+        &format!("launcher/{}/{}/index.json", known.launcher_key, known.id),
+    );
+    let launcher: LauncherIndex = cached_request(&launcher_url).await?;
+
+    // TODO: language selection
+    let url = join_url(
+        &api_url(),
+        &format!(
+            "launcher/{}/{}/background/{}/en.json",
+            known.launcher_key, known.id, launcher.function_code.background
+        ),
+    );
+
+    cached_request(&url).await
+}
+
+// Unlike HoYoPlay, the index changes in place on every update, so the cache
+// is only used as a fallback when the request fails
+async fn cached_request<Type>(url: &str) -> Result<Type, String>
+where
+    Type: for<'a> Deserialize<'a> + Serialize,
+{
+    // Not borrowing the settings across the request, they may be saved meanwhile
+    let cache_path = &cache_directory()?;
+
+    match fetch_json::<Type>(url).await {
+        Ok(data) => {
+            match serde_json::to_vec(&data) {
+                Ok(bytes) => {
+                    if let Err(e) = cacache::write_sync(cache_path, url, bytes) {
+                        println!("Error when writing cache entry for {url}: {e}");
+                    }
+                }
+                Err(e) => println!("Error when serializing cache entry for {url}: {e}"),
+            }
+            Ok(data)
+        }
+        Err(e) => {
+            let asset = cacache::read_sync(cache_path, url).map_err(|_| e)?;
+            serde_json::from_slice(&asset).map_err(|e| format!("Cache parse error: {e}"))
+        }
+    }
+}
+
+async fn fetch_json<Type>(url: &str) -> Result<Type, String>
+where
+    Type: for<'a> Deserialize<'a>,
+{
+    let response = reqwest::get(url).await.map_err(|e| {
+        set_offline();
+        format!("Api request error: {e}")
+    })?;
+
+    if !response.status().is_success() {
+        return Err(format!(
+            "Api request failed with status: {}",
+            response.status()
+        ));
+    }
+
+    response
+        .json()
+        .await
+        .map_err(|e| format!("Api parse error: {e}"))
+}
+
+fn cdn_url(release: &GameRelease, endpoints: &Endpoints) -> Result<String, String> {
+    if let Some(url) = endpoints.kuro() {
+        return Ok(url);
+    }
+
+    release
+        .cdn_list
+        .iter()
+        .min_by_key(|cdn| cdn.priority)
+        .map(|cdn| cdn.url.clone())
+        .ok_or_else(|| "No CDN available for this release".to_string())
+}
+
+async fn get_resources(
+    release: &GameRelease,
+    endpoints: &Endpoints,
+) -> Result<ResourceList, String> {
+    let cdn = cdn_url(release, endpoints)?;
+
+    fetch_json(&join_url(&cdn, &release.resources)).await
+}
+
+/// Installs or updates a game: every file that is missing or doesn't match
+/// its checksum is downloaded again.
+pub async fn install_game(
+    release: &GameRelease,
+    install_path: &Path,
+    endpoints: &Endpoints,
+) -> Result<(), String> {
+    let cdn = cdn_url(release, endpoints)?;
+    let resources = get_resources(release, endpoints).await?;
+    let base_url = join_url(&cdn, &release.resources_base_path);
+
+    for resource in &resources.resource {
+        let target = resource_path(install_path, resource)?;
+        if verify_resource(&target, resource)? {
+            continue;
+        }
+
+        println!("Downloading {}..", resource.dest);
+        download_file(&join_url(&base_url, &resource.dest), &target).await?;
+
+        if !verify_resource(&target, resource)? {
+            let _ = fs::remove_file(&target);
+            return Err(format!("Checksum mismatch for {}", resource.dest));
+        }
+    }
+
+    Ok(())
+}
+
+fn resource_path(install_path: &Path, resource: &Resource) -> Result<PathBuf, String> {
+    let dest = Path::new(resource.dest.trim_start_matches('/'));
+    if dest
+        .components()
+        .any(|component| !matches!(component, Component::Normal(_)))
+    {
+        return Err(format!(
+            "Refusing to write outside of install path: {}",
+            resource.dest
+        ));
+    }
+
+    Ok(install_path.join(dest))
+}
+
+fn verify_resource(path: &Path, resource: &Resource) -> Result<bool, String> {
+    let Ok(metadata) = fs::metadata(path) else {
+        return Ok(false);
+    };
+    if metadata.len() != resource.size {
+        return Ok(false);
+    }
+
+    md5_matches(path, &resource.md5)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GAME: &str = "launcher/game/G153/50004_obOHXFrFanqsaIEOmuKroCcbZkQRBC7c";
+
+    fn fixture(path: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("fixtures/kuro")
+            .join(path)
+    }
+
+    fn read_fixture<Type: for<'a> Deserialize<'a>>(path: &str) -> Type {
+        let text = fs::read_to_string(fixture(path)).unwrap();
+        serde_json::from_str(&text).unwrap()
+    }
+
+    #[test]
+    fn parses_index() {
+        let index: GameIndex = read_fixture(&format!("{GAME}/index.json"));
+
+        assert_eq!(index.default.version, "2.0.0");
+        assert_eq!(
+            index.default.resources,
+            format!("{GAME}/resource/2.0.0/resource.json")
+        );
+        assert_eq!(
+            index.default.resources_base_path,
+            format!("{GAME}/zip/2.0.0")
+        );
+        assert!(index.predownload.is_none());
+        assert_eq!(
+            cdn_url(&index.default, &Endpoints::default()).unwrap(),
+            "https://hw-pcdownload-aws.aki-game.net/"
+        );
+    }
+
+    #[test]
+    fn verifies_resources() {
+        let resources: ResourceList = read_fixture(&format!("{GAME}/resource/2.0.0/resource.json"));
+        let [resource] = resources.resource.as_slice() else {
+            panic!("Expected a single resource");
+        };
+
+        let base = fixture(&format!("{GAME}/zip/2.0.0"));
+        let path = resource_path(&base, resource).unwrap();
+        assert_eq!(path, base.join("Wuthering Waves.exe"));
+        assert!(verify_resource(&path, resource).unwrap());
+
+        let changed = Resource {
+            md5: "00000000000000000000000000000000".to_string(),
+            ..resource.clone()
+        };
+        assert!(!verify_resource(&path, &changed).unwrap());
+
+        let resized = Resource {
+            size: resource.size + 1,
+            ..resource.clone()
+        };
+        assert!(!verify_resource(&path, &resized).unwrap());
+        assert!(!verify_resource(&base.join("missing.exe"), resource).unwrap());
+    }
+
+    #[test]
+    fn rejects_paths_outside_of_install() {
+        let resource = Resource {
+            dest: "/../escape.exe".to_string(),
+            md5: String::new(),
+            size: 0,
+        };
+
+        assert!(resource_path(Path::new("/games/wuwa"), &resource).is_err());
+    }
+}
@@ -1,31 +1,464 @@
-use std::{env, path::PathBuf, sync::LazyLock};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    sync::{LazyLock, RwLock},
+};
 
-use crate::utils::filesystem::{ensure_dir, ensure_writable};
+use serde_json::Value;
 
-pub static DATA_PATH: LazyLock<PathBuf> = LazyLock::new(init_data_path);
-pub static CONFIG_PATH: LazyLock<PathBuf> = LazyLock::new(|| DATA_PATH.join("./config.json"));
+use crate::{
+    settings::migrations::schema_version,
+    utils::filesystem::{ensure_dir, ensure_writable, move_directory, move_file},
+};
 
-fn init_data_path() -> PathBuf {
-    let try_env = |var: &str, suffix: &str| {
-        env::var(var)
-            .map_err(|e| format!("Cannot read env var: {e}"))
-            .and_then(|dir| {
-                let path = PathBuf::from(dir).join(suffix);
-                ensure_dir(&path)?;
-                ensure_writable(&path)?;
+/// Everything is kept in here when set, from `ELYSIA_HOME` or a `portable`
+/// file next to the executable
+pub static PORTABLE_HOME: LazyLock<Option<PathBuf>> = LazyLock::new(|| {
+    let executable_directory = env::current_exe()
+        .ok()
+        .and_then(|executable| executable.parent().map(Path::to_path_buf));
+    portable_home(
+        env::var_os("ELYSIA_HOME").map(PathBuf::from),
+        env::current_dir().ok(),
+        executable_directory,
+    )
+});
+/// Set by `resolve_data_path`, see `paths`
+static PATHS: RwLock<Option<Paths>> = RwLock::new(None);
 
-                Ok(path)
-            })
+/// The portable home from `ELYSIA_HOME`, relative to `current_dir`, or the
+/// executable directory when it has a `portable` file.
+fn portable_home(
+    elysia_home: Option<PathBuf>,
+    current_dir: Option<PathBuf>,
+    executable_directory: Option<PathBuf>,
+) -> Option<PathBuf> {
+    if let Some(home) = elysia_home.filter(|home| !home.as_os_str().is_empty()) {
+        return Some(match current_dir {
+            // Joining keeps absolute paths as they are
+            Some(current) => current.join(home),
+            None => home,
+        });
+    }
+
+    executable_directory.filter(|directory| directory.join("portable").is_file())
+}
+
+/// What the paths are resolved from, read once by `Environment::current`.
+/// Empty variables count as unset.
+#[derive(Debug, Clone, Default)]
+pub struct Environment {
+    pub portable_home: Option<PathBuf>,
+    pub xdg_data_home: Option<PathBuf>,
+    pub xdg_config_home: Option<PathBuf>,
+    pub xdg_cache_home: Option<PathBuf>,
+    pub xdg_state_home: Option<PathBuf>,
+    pub home: Option<PathBuf>,
+    pub pwd: Option<PathBuf>,
+}
+
+impl Environment {
+    pub fn current() -> Self {
+        let var = |name: &str| {
+            env::var_os(name)
+                .filter(|value| !value.is_empty())
+                .map(PathBuf::from)
+        };
+
+        Self {
+            portable_home: PORTABLE_HOME.clone(),
+            xdg_data_home: var("XDG_DATA_HOME"),
+            xdg_config_home: var("XDG_CONFIG_HOME"),
+            xdg_cache_home: var("XDG_CACHE_HOME"),
+            xdg_state_home: var("XDG_STATE_HOME"),
+            home: var("HOME"),
+            pwd: var("PWD"),
+        }
+    }
+
+    /// `$<xdg_home>/elysia`, or `$HOME/<home_suffix>/elysia` when it's unset.
+    fn xdg_directory(&self, xdg_home: &Option<PathBuf>, home_suffix: &str) -> Option<PathBuf> {
+        xdg_home
+            .clone()
+            .or_else(|| self.home.as_ref().map(|home| home.join(home_suffix)))
+            .map(|directory| directory.join("elysia"))
+    }
+
+    /// Places to keep the data in, by name and in the order they're tried.
+    fn data_candidates(&self, chosen: Option<PathBuf>) -> Vec<(String, Result<PathBuf, String>)> {
+        let from_var = |var: &str, value: &Option<PathBuf>, suffix: &str| {
+            value
+                .as_ref()
+                .map(|directory| directory.join(suffix))
+                .ok_or_else(|| format!("{var} isn't set"))
+        };
+
+        match (chosen, &self.portable_home) {
+            (Some(chosen), _) => vec![(chosen.to_string_lossy().into_owned(), Ok(chosen))],
+            // Not falling back to the usual directories, that's what it's meant to avoid
+            (None, Some(home)) => vec![("Portable home".to_string(), Ok(home.clone()))],
+            (None, None) => vec![
+                (
+                    "$XDG_DATA_HOME/elysia".to_string(),
+                    from_var("XDG_DATA_HOME", &self.xdg_data_home, "elysia"),
+                ),
+                (
+                    "$HOME/.local/share/elysia".to_string(),
+                    from_var("HOME", &self.home, ".local/share/elysia"),
+                ),
+                (
+                    "$PWD/elysia".to_string(),
+                    from_var("PWD", &self.pwd, "elysia"),
+                ),
+            ],
+        }
+    }
+}
+
+/// Where the launcher keeps its files, everything but the data directory
+/// follows the XDG base directories unless it's portable.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Paths {
+    /// Games, prefixes and components
+    pub data: PathBuf,
+    pub config: PathBuf,
+    /// Default cacache store
+    pub cache: PathBuf,
+    /// Logs and state that isn't configuration, like the download queue
+    pub state: PathBuf,
+    /// Default directory of downloads in progress, next to the cache so they
+    /// survive a reboot
+    pub temp: PathBuf,
+}
+
+impl Paths {
+    /// The paths of the data directory `data`. XDG directories `usable`
+    /// refuses are replaced by the data directory.
+    fn derive(data: &Path, environment: &Environment, usable: impl Fn(&Path) -> bool) -> Self {
+        if let Some(home) = &environment.portable_home {
+            return Self {
+                data: data.to_path_buf(),
+                config: home.clone(),
+                cache: home.join("cache"),
+                state: home.clone(),
+                temp: home.join("temp"),
+            };
+        }
+
+        let xdg = |xdg_home: &Option<PathBuf>, home_suffix: &str| {
+            environment
+                .xdg_directory(xdg_home, home_suffix)
+                .filter(|directory| usable(directory))
+        };
+        let cache =
+            xdg(&environment.xdg_cache_home, ".cache").unwrap_or_else(|| data.join("cache"));
+
+        Self {
+            data: data.to_path_buf(),
+            config: xdg(&environment.xdg_config_home, ".config")
+                .unwrap_or_else(|| data.to_path_buf()),
+            temp: cache.join("downloads"),
+            cache,
+            state: xdg(&environment.xdg_state_home, ".local/state")
+                .unwrap_or_else(|| data.to_path_buf()),
+        }
+    }
+
+    pub fn config_file(&self) -> PathBuf {
+        self.config.join("config.json")
+    }
+
+    pub fn config_backups(&self) -> PathBuf {
+        self.config.join("backups")
+    }
+
+    pub fn download_queue(&self) -> PathBuf {
+        self.state.join("downloads.json")
+    }
+}
+
+/// The paths found by `resolve_data_path`.
+pub fn paths() -> Result<Paths, String> {
+    PATHS
+        .read()
+        .ok()
+        .and_then(|paths| paths.clone())
+        .ok_or_else(|| "The data directory isn't resolved yet".to_string())
+}
+
+pub fn data_path() -> Result<PathBuf, String> {
+    paths().map(|paths| paths.data)
+}
+
+/// `path` as written to the config and the download queue, relative to the
+/// data directory in portable mode so the portable home can be moved.
+pub fn to_stored_path(path: &Path) -> PathBuf {
+    match (&*PORTABLE_HOME, data_path()) {
+        (Some(_), Ok(data_path)) => path
+            .strip_prefix(data_path)
+            .map(Path::to_path_buf)
+            .unwrap_or_else(|_| path.to_path_buf()),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// A path written by `to_stored_path`, absolute paths stay as they are.
+/// Relative ones stay relative until the data directory is resolved.
+pub fn from_stored_path(path: &Path) -> PathBuf {
+    match data_path() {
+        Ok(data_path) => data_path.join(path),
+        Err(_) => path.to_path_buf(),
+    }
+}
+
+/// A place that was tried for data files and why it can't be used.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PathAttempt {
+    pub name: String,
+    pub error: String,
+}
+
+/// Finds a writable data directory, or uses the one `chosen` by the user,
+/// and keeps it with the paths derived from it until another one is chosen.
+/// This runs before anything else touches the disk.
+pub fn resolve_data_path(chosen: Option<PathBuf>) -> Result<PathBuf, Vec<PathAttempt>> {
+    if chosen.is_none()
+        && let Ok(path) = data_path()
+    {
+        return Ok(path);
+    }
+
+    let environment = Environment::current();
+    let mut attempts = Vec::new();
+    for (name, path) in environment.data_candidates(chosen) {
+        let usable = path.and_then(|path| {
+            ensure_dir(&path)?;
+            ensure_writable(&path)?;
+
+            Ok(path)
+        });
+        match usable {
+            Ok(path) => {
+                let paths = Paths::derive(&path, &environment, |directory| {
+                    ensure_dir(directory)
+                        .and_then(|()| ensure_writable(directory))
+                        .inspect_err(|e| println!("{e}"))
+                        .is_ok()
+                });
+                if let Ok(mut resolved) = PATHS.write() {
+                    *resolved = Some(paths);
+                }
+                return Ok(path);
+            }
+            Err(error) => attempts.push(PathAttempt { name, error }),
+        }
+    }
+
+    Err(attempts)
+}
+
+/// Moves the config and download queue out of the data directory, where
+/// everything used to live.
+pub fn migrate_legacy_layout() {
+    let paths = match paths() {
+        Ok(paths) => paths,
+        Err(e) => {
+            println!("{e}");
+            return;
+        }
+    };
+    let data_path = &paths.data;
+    let mut moves = vec![
+        (data_path.join("config.json"), paths.config_file()),
+        (data_path.join("downloads.json"), paths.download_queue()),
+    ];
+    // Copies kept by schema migrations and broken configs
+    if let Ok(entries) = fs::read_dir(data_path) {
+        moves.extend(
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.file_name().to_string_lossy().into_owned())
+                .filter(|name| name.starts_with("config.") && name != "config.json")
+                .map(|name| (data_path.join(&name), paths.config.join(&name))),
+        );
+    }
+
+    for (from, to) in moves {
+        if from == to || !from.exists() || to.exists() {
+            continue;
+        }
+        match move_file(&from, &to) {
+            Ok(()) => println!("Moved {from:?} to {to:?}"),
+            Err(e) => println!("{e}"),
+        }
+    }
+
+    let backups = data_path.join("backups");
+    let config_backups = paths.config_backups();
+    if backups != config_backups
+        && let Err(e) = move_directory(&backups, &config_backups)
+    {
+        println!("{e}");
+    }
+
+    // Schema 2 only rewrites the path of the cache, it's moved here
+    let old_cache = data_path.join("cache");
+    if old_cache != paths.cache
+        && uses_legacy_cache(&paths.config_file(), &old_cache)
+        && let Err(e) = move_directory(&old_cache, &paths.cache)
+    {
+        println!("Cannot move the cache, starting a new one: {e}");
+    }
+}
+
+/// Whether the config at `config_path` is older than schema 2 and keeps the
+/// cache at its old default.
+fn uses_legacy_cache(config_path: &Path, old_cache: &Path) -> bool {
+    let Some(config) = fs::read(config_path)
+        .ok()
+        .and_then(|data| serde_json::from_slice::<Value>(&data).ok())
+    else {
+        return false;
     };
 
-    let data_dir = try_env("XDG_DATA_HOME", "elysia")
-        .or_else(|_| try_env("HOME", ".local/share/elysia"))
-        .or_else(|_| try_env("PWD", "elysia"));
+    let before_xdg = schema_version(&config).is_ok_and(|version| version < 2);
+    let cache = config.get("cache_directory").and_then(Value::as_str);
+
+    before_xdg && cache.is_none_or(|cache| Path::new(cache) == old_cache)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(path: &str) -> PathBuf {
+        PathBuf::from(path)
+    }
+
+    fn xdg_environment() -> Environment {
+        Environment {
+            xdg_data_home: Some(path("/xdg/data")),
+            xdg_config_home: Some(path("/xdg/config")),
+            xdg_cache_home: Some(path("/xdg/cache")),
+            xdg_state_home: Some(path("/xdg/state")),
+            home: Some(path("/home/user")),
+            pwd: Some(path("/work")),
+            ..Environment::default()
+        }
+    }
+
+    #[test]
+    fn elysia_home_is_relative_to_the_current_directory() {
+        let home = portable_home(Some(path("home")), Some(path("/work")), None);
+        assert_eq!(home, Some(path("/work/home")));
+
+        let home = portable_home(Some(path("/elysia")), Some(path("/work")), None);
+        assert_eq!(home, Some(path("/elysia")));
+    }
+
+    #[test]
+    fn portable_file_makes_the_executable_directory_the_home() {
+        let directory =
+            env::temp_dir().join(format!("elysia-portable-test-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+
+        assert_eq!(portable_home(None, None, Some(directory.clone())), None);
+        // An empty ELYSIA_HOME is unset
+        fs::write(directory.join("portable"), "").unwrap();
+        assert_eq!(
+            portable_home(Some(PathBuf::new()), None, Some(directory.clone())),
+            Some(directory.clone())
+        );
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn portable_mode_keeps_everything_in_the_home() {
+        let environment = Environment {
+            portable_home: Some(path("/portable")),
+            ..xdg_environment()
+        };
+
+        let candidates = environment.data_candidates(None);
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(candidates[0].1, Ok(path("/portable")));
+
+        let paths = Paths::derive(Path::new("/portable"), &environment, |_| true);
+        assert_eq!(
+            paths,
+            Paths {
+                data: path("/portable"),
+                config: path("/portable"),
+                cache: path("/portable/cache"),
+                state: path("/portable"),
+                temp: path("/portable/temp"),
+            }
+        );
+        assert_eq!(paths.config_file(), path("/portable/config.json"));
+        assert_eq!(paths.download_queue(), path("/portable/downloads.json"));
+    }
+
+    #[test]
+    fn follows_the_xdg_base_directories() {
+        let environment = xdg_environment();
+
+        let candidates = environment.data_candidates(None);
+        assert_eq!(candidates[0].1, Ok(path("/xdg/data/elysia")));
+
+        let paths = Paths::derive(Path::new("/xdg/data/elysia"), &environment, |_| true);
+        assert_eq!(
+            paths,
+            Paths {
+                data: path("/xdg/data/elysia"),
+                config: path("/xdg/config/elysia"),
+                cache: path("/xdg/cache/elysia"),
+                state: path("/xdg/state/elysia"),
+                temp: path("/xdg/cache/elysia/downloads"),
+            }
+        );
+        assert_eq!(paths.config_backups(), path("/xdg/config/elysia/backups"));
+    }
+
+    #[test]
+    fn falls_back_to_home_without_xdg_variables() {
+        let environment = Environment {
+            home: Some(path("/home/user")),
+            ..Environment::default()
+        };
+
+        let candidates = environment.data_candidates(None);
+        assert_eq!(candidates[0].1, Err("XDG_DATA_HOME isn't set".to_string()));
+        assert_eq!(candidates[1].1, Ok(path("/home/user/.local/share/elysia")));
+        assert_eq!(candidates[2].1, Err("PWD isn't set".to_string()));
+
+        let data = path("/home/user/.local/share/elysia");
+        let paths = Paths::derive(&data, &environment, |_| true);
+        assert_eq!(paths.config, path("/home/user/.config/elysia"));
+        assert_eq!(paths.cache, path("/home/user/.cache/elysia"));
+        assert_eq!(paths.state, path("/home/user/.local/state/elysia"));
+    }
+
+    #[test]
+    fn uses_the_data_directory_for_unusable_xdg_directories() {
+        let environment = xdg_environment();
+        let data = path("/data");
+
+        let paths = Paths::derive(&data, &environment, |directory| {
+            !directory.starts_with("/xdg/cache")
+        });
+        assert_eq!(paths.config, path("/xdg/config/elysia"));
+        assert_eq!(paths.cache, path("/data/cache"));
+        assert_eq!(paths.temp, path("/data/cache/downloads"));
+    }
+
+    #[test]
+    fn chosen_directory_is_the_only_candidate() {
+        let candidates = xdg_environment().data_candidates(Some(path("/chosen")));
 
-    // TODO: show this error in a popup
-    data_dir.unwrap_or_else(|_| {
-        panic!(
-            "Cannot find a writable directory for data files (tried: $XDG_DATA_HOME/elysia, $HOME/.local/share/elysia, $PWD/elysia)"
-        )
-    })
+        assert_eq!(
+            candidates,
+            vec![("/chosen".to_string(), Ok(path("/chosen")))]
+        );
+    }
 }
@@ -6,10 +6,12 @@ use freya_router::prelude::*;
 use reqwest::Url;
 
 use crate::{
-    Context,
-    components::{Expand, MyNetworkImage, MySidebarItem},
-    game_providers::hoyoplay::api::Game,
-    pages::{ErrorPage, Game, Home},
+    components::{
+        Expand, MyButton, MyConfigRecovery, MyDownloadIndicator, MyNetworkImage, MySidebarItem,
+    },
+    context::{CacheRefresh, Connectivity, Context},
+    pages::{AddCustomGame, Downloads, ErrorPage, Game, GameSettings, Home, Settings},
+    settings::GlobalSettings,
 };
 
 #[derive(Routable, Clone, PartialEq)]
@@ -18,8 +20,16 @@ pub enum Route {
     #[layout(AppLayout)]
         #[route("/")]
         Home,
+        #[route("/downloads")]
+        Downloads,
+        #[route("/settings")]
+        Settings,
+        #[route("/games/new")]
+        AddCustomGame,
         #[route("/games/:game_id")]
         Game {game_id: String},
+        #[route("/games/:game_id/settings")]
+        GameSettings {game_id: String},
     #[end_layout]
     #[route("/..route")]
     ErrorPage {},
@@ -79,6 +89,8 @@ fn FromRouteToCurrent(
 fn AnimatedOutlet(children: Element) -> Element {
     let (reference, node_size) = use_node_signal();
     let animated_router = use_context::<Signal<AnimatedRouterContext<Route>>>();
+    let ctx_signal = use_context::<Signal<Option<Context>>>();
+    let settings = use_context::<Signal<GlobalSettings>>();
 
     let from_route = match animated_router() {
         AnimatedRouterContext::FromTo(Route::Home, Route::Game { game_id: id }) => {
@@ -88,16 +100,13 @@ fn AnimatedOutlet(children: Element) -> Element {
             Route::Game { game_id: id1 },
             Route::Game { game_id: id2 },
         ) => {
-            let ctx = &use_context::<Context>();
-            let games = &ctx.api_games;
-            let order = games
-                .iter()
-                .position(|game| game.id == id1)
-                .unwrap_or_default();
-            let order2 = games
-                .iter()
-                .position(|game| game.id == id2)
-                .unwrap_or_default();
+            let ctx = &ctx_signal.read().clone().unwrap_or_default();
+            let settings = &settings.read();
+            let mut games = ctx.game_ids();
+            games.extend(installed_only_ids(ctx, settings));
+            games.extend(settings.custom_game_ids());
+            let order = games.iter().position(|id| *id == id1).unwrap_or_default();
+            let order2 = games.iter().position(|id| *id == id2).unwrap_or_default();
             let upwards = order < order2;
             Some((rsx!(Game { game_id: id1 }), upwards))
         }
@@ -122,53 +131,132 @@ fn AnimatedOutlet(children: Element) -> Element {
     )
 }
 
-fn make_links(games: &[Game]) -> Vec<Element> {
-    games
+fn make_links(ctx: &Context) -> Vec<Element> {
+    let hoyoplay = ctx
+        .api_games
         .iter()
+        .filter(|game| game.display_status.is_visible())
         .map(|game| {
-            let route = Route::Game {
-                game_id: game.id.clone(),
-            };
-
-            rsx!(
-                Link {
-                    key: game.id.clone(),
-                    to: route.clone(),
-
-                    ActivableRoute {
-                        route: route,
-                        exact: true,
-                        MySidebarItem {
-                            match game.display.icon.url.parse::<Url>() {
-                                Ok(url) => rsx!(
-                                        MyNetworkImage {
-                                        url: url,
-                                        aspect_ratio: "max",
-                                        cover: "center",
-                                        width: "48",
-                                        height: "48",
-                                        sampling: "catmull-rom"
-                                    }
-                                ),
-                                Err(_) => rsx!(
-                                    rect {
-                                        label {
-                                            {game.display.name.clone()}
-                                        }
-                                    }
-                                )
+            let badge = (!game.display_status.is_released()).then_some("Soon");
+            make_link(&game.id, &game.display.name, &game.display.icon.url, badge)
+        });
+    let kuro = ctx.kuro_games.iter().map(|game| {
+        let icon = game
+            .background
+            .as_ref()
+            .map(|background| background.background_file.as_str())
+            .unwrap_or_default();
+        make_link(&game.id, &game.name, icon, None)
+    });
+
+    hoyoplay.chain(kuro).collect::<Vec<_>>()
+}
+
+/// Installed games the api doesn't list right now, e.g. while offline.
+fn installed_only_ids(ctx: &Context, settings: &GlobalSettings) -> Vec<String> {
+    let known = ctx.game_ids();
+    let mut ids = settings
+        .installed_games
+        .keys()
+        .filter(|id| !known.contains(id) && !settings.custom_games.contains_key(*id))
+        .cloned()
+        .collect::<Vec<_>>();
+    ids.sort();
+
+    ids
+}
+
+fn make_installed_links(ctx: &Context, settings: &GlobalSettings) -> Vec<Element> {
+    installed_only_ids(ctx, settings)
+        .iter()
+        .map(|id| match ctx.offline_games.get(id) {
+            Some(summary) => make_link(
+                id,
+                &summary.name,
+                summary.icon.as_deref().unwrap_or_default(),
+                None,
+            ),
+            None => make_link(id, &settings.installed_games[id].biz_name, "", None),
+        })
+        .collect::<Vec<_>>()
+}
+
+fn make_custom_links(settings: &GlobalSettings) -> Vec<Element> {
+    settings
+        .custom_game_ids()
+        .iter()
+        .map(|id| {
+            let game = &settings.custom_games[id];
+            let icon = game
+                .icon
+                .as_ref()
+                .and_then(|icon| Url::from_file_path(icon).ok())
+                .map(|url| url.to_string())
+                .unwrap_or_default();
+            make_link(id, &game.name, &icon, None)
+        })
+        .collect::<Vec<_>>()
+}
+
+fn make_link(id: &str, name: &str, icon: &str, badge: Option<&str>) -> Element {
+    let route = Route::Game {
+        game_id: id.to_string(),
+    };
+
+    rsx!(
+        Link {
+            key: "{id}",
+            to: route.clone(),
+
+            ActivableRoute {
+                route: route,
+                exact: true,
+                MySidebarItem {
+                    match icon.parse::<Url>() {
+                        Ok(url) => rsx!(
+                                MyNetworkImage {
+                                url: url,
+                                aspect_ratio: "max",
+                                cover: "center",
+                                width: "48",
+                                height: "48",
+                                sampling: "catmull-rom"
+                            }
+                        ),
+                        Err(_) => rsx!(
+                            rect {
+                                label {
+                                    "{name}"
+                                }
+                            }
+                        )
+                    }
+                    if let Some(badge) = badge {
+                        rect {
+                            position: "absolute",
+                            position_bottom: "0",
+                            position_right: "0",
+                            padding: "1 4",
+                            corner_radius: "4",
+                            background: "rgb(34,34,34,0.8)",
+                            font_size: "10",
+                            label {
+                                "{badge}"
                             }
                         }
                     }
                 }
-            )
-        })
-        .collect::<Vec<_>>()
+            }
+        }
+    )
 }
 
 #[allow(non_snake_case)]
 fn AppLayout() -> Element {
-    let ctx_resource = &use_context::<Resource<Context>>();
+    let ctx_signal = use_context::<Signal<Option<Context>>>();
+    let settings = use_context::<Signal<GlobalSettings>>();
+    let mut cache_refresh = use_context::<Signal<CacheRefresh>>();
+    let connectivity = use_context::<Signal<Connectivity>>();
 
     rsx! {
         NativeRouter {
@@ -192,15 +280,22 @@ fn AppLayout() -> Element {
                         ScrollView {
                             padding: "8",
                             spacing: "8",
-                            height: "90%",
-                            match &*ctx_resource.read_unchecked() {
-                                Some(ctx) => {
-                                    use_context_provider(|| ctx.clone());
-                                    rsx! {
-                                        for route in make_links(&ctx.api_games) {
-                                            {route}
+                            height: "80%",
+                            match &*ctx_signal.read() {
+                                Some(ctx) => rsx! {
+                                    if ctx.api_games_error.is_some() {
+                                        label {
+                                            font_size: "11",
+                                            color: "rgb(255, 120, 120)",
+                                            "Game list unavailable"
                                         }
                                     }
+                                    for route in make_links(ctx) {
+                                        {route}
+                                    }
+                                    for route in make_installed_links(ctx, &settings.read()) {
+                                        {route}
+                                    }
                                 },
                                 _ => rsx! {
                                     label {
@@ -208,19 +303,76 @@ fn AppLayout() -> Element {
                                     }
                                 }
                             }
+                            for route in make_custom_links(&settings.read()) {
+                                {route}
+                            }
+                            Link {
+                                key: "add-game",
+                                to: Route::AddCustomGame,
+
+                                ActivableRoute {
+                                    route: Route::AddCustomGame,
+                                    exact: true,
+                                    MySidebarItem {
+                                        rect {
+                                            width: "48",
+                                            height: "48",
+                                            main_align: "center",
+                                            cross_align: "center",
+                                            font_size: "32",
+                                            label {
+                                                "+"
+                                            }
+                                        }
+                                    }
+                                }
+                            }
                         }
                         rect {
-                            height: "10%",
+                            height: "20%",
                             width: "100%",
                             font_size: "40",
                             main_align: "center",
                             cross_align: "center",
+                            spacing: "8",
+                            if connectivity() == Connectivity::Offline {
+                                label {
+                                    font_size: "12",
+                                    color: "rgb(255, 180, 80)",
+                                    "Offline"
+                                }
+                            }
+                            Link {
+                                key: "downloads",
+                                to: Route::Downloads,
+
+                                ActivableRoute {
+                                    route: Route::Downloads,
+                                    exact: true,
+                                    MySidebarItem {
+                                        MyDownloadIndicator {}
+                                    }
+                                }
+                            }
+                            MyButton {
+                                onpress: move |_| {
+                                    let generation = cache_refresh.peek().generation;
+                                    cache_refresh.set(CacheRefresh {
+                                        generation: generation + 1,
+                                        bypass: true,
+                                    });
+                                },
+                                label {
+                                    font_size: "24",
+                                    "⟳"
+                                }
+                            }
                             Link {
                                 key: "settings",
-                                to: Route::Home,
+                                to: Route::Settings,
 
                                 ActivableRoute {
-                                    route: Route::Home,
+                                    route: Route::Settings,
                                     exact: true,
                                     MySidebarItem {
                                         label {
@@ -240,6 +392,15 @@ fn AppLayout() -> Element {
                         Body {
                             AnimatedOutlet { }
                         }
+                        rect {
+                            position: "absolute",
+                            position_top: "0",
+                            position_left: "0",
+                            width: "100%",
+                            padding: "16 16 0 100",
+                            layer: "-20",
+                            MyConfigRecovery {}
+                        }
                     }
                 }
 
@@ -0,0 +1,12 @@
+//! The launcher itself, `main.rs` only opens the window. Split out so the
+//! integration tests in `tests/` can reach the api clients.
+
+pub mod components;
+pub mod context;
+pub mod game_providers;
+pub mod globals;
+pub mod layout;
+pub mod pages;
+pub mod runners;
+pub mod settings;
+pub mod utils;
@@ -3,29 +3,30 @@
     windows_subsystem = "windows"
 )]
 
-mod components;
-mod context;
-mod game_providers;
-mod globals;
-mod layout;
-mod pages;
-mod runners;
-mod settings;
-mod utils;
-
 use std::collections::HashMap;
 use std::path::PathBuf;
+use std::time::Duration;
 
 use freya::prelude::*;
 
-use crate::{
-    context::Context,
-    game_providers::hoyoplay::{get_game_content, get_games},
-    runners::{Runner, Wine},
-    settings::{GlobalSettings, InstalledGame, RuntimeComponent},
-    utils::umu::setup_umu,
+use elysia::{
+    context::{CacheRefresh, ConfigRecovery, Connectivity, Context, NewsStore},
+    game_providers::{
+        hoyoplay::{check_connectivity, get_games},
+        kuro,
+    },
+    globals::{PathAttempt, migrate_legacy_layout, resolve_data_path},
+    layout,
+    pages::{ChosenDirectory, StartupError, StartupErrorPage},
+    settings::{Directory, GlobalSettings, LoadError, UnusableDirectory, backups, block_saving},
+    utils::{
+        cache::CachePolicy, download::DownloadManager, installs::Installs, umu::setup_umu,
+        umu_database,
+    },
 };
 
+const CONNECTIVITY_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
 fn main() {
     launch_cfg(
         LaunchConfig::new().with_window(
@@ -39,36 +40,127 @@ fn main() {
     );
 }
 
-fn app() -> Element {
-    let mut settings = use_signal(|| match GlobalSettings::load() {
-        Ok(mut settings) => {
-            settings.validate();
-            settings
+/// Everything that has to work before the launcher shows up. `chosen` is a
+/// data directory picked on the startup error page, and `overrides` replace
+/// directories of the config that couldn't be used.
+fn start(
+    chosen: Option<PathBuf>,
+    overrides: &HashMap<Directory, PathBuf>,
+) -> Result<(GlobalSettings, Option<ConfigRecovery>), StartupError> {
+    resolve_data_path(chosen).map_err(StartupError::DataPath)?;
+
+    load_settings(overrides).map_err(StartupError::Settings)
+}
+
+/// The saved config, or the defaults and what went wrong when it's broken.
+fn load_settings(
+    overrides: &HashMap<Directory, PathBuf>,
+) -> Result<(GlobalSettings, Option<ConfigRecovery>), UnusableDirectory> {
+    migrate_legacy_layout();
+
+    let (mut settings, recovery, save) = match GlobalSettings::load() {
+        Ok(Some(settings)) => (settings, None, false),
+        Ok(None) => (GlobalSettings::default(), None, true),
+        Err(LoadError::Newer(error)) => {
+            println!("{error}");
+            // Saving would replace it with a config the newer version can't use fully
+            block_saving(true);
+
+            let recovery = ConfigRecovery {
+                error,
+                corrupted: None,
+                backup: None,
+                newer_version: true,
+            };
+            (GlobalSettings::default(), Some(recovery), false)
         }
-        Err(_) => {
-            let mut settings = GlobalSettings::default();
-            settings.validate();
-            settings.save();
-            settings
+        Err(LoadError::Unreadable(error)) => {
+            println!("Cannot load config, starting with the defaults: {error}");
+            let corrupted = backups::keep_corrupted()
+                .inspect_err(|e| println!("{e}"))
+                .ok();
+            // Saving over a config that couldn't be moved would lose it, until
+            // a backup is restored or the defaults are kept
+            block_saving(corrupted.is_none());
+
+            let recovery = ConfigRecovery {
+                error,
+                corrupted,
+                backup: backups::latest(),
+                newer_version: false,
+            };
+            (GlobalSettings::default(), Some(recovery), true)
         }
-    });
+    };
+
+    for (directory, path) in overrides {
+        *directory.path_mut(&mut settings) = path.clone();
+    }
+    settings.validate()?;
+    if save || !overrides.is_empty() {
+        settings.save();
+    }
+
+    Ok((settings, recovery))
+}
+
+fn app() -> Element {
+    use_init_theme(|| DARK_THEME);
+
+    // Every directory picked on the startup error page, kept across retries
+    let mut overrides = use_signal(HashMap::<Directory, PathBuf>::new);
+    let mut startup = use_signal(|| start(None, &HashMap::new()));
+
+    let onretry = move |chosen: Option<ChosenDirectory>| {
+        let data_path = match chosen {
+            Some(ChosenDirectory::Data(path)) => Some(path),
+            Some(ChosenDirectory::Settings(directory, path)) => {
+                overrides.write().insert(directory, path);
+                None
+            }
+            None => None,
+        };
+
+        let result = match (start(data_path, &overrides.read()), &*startup.peek()) {
+            // Keeps showing the places that failed before
+            (Err(StartupError::DataPath(attempts)), Err(StartupError::DataPath(earlier))) => {
+                Err(StartupError::DataPath(merge_attempts(earlier, attempts)))
+            }
+            (result, _) => result,
+        };
+        startup.set(result);
+    };
 
-    settings.write().installed_games.insert(
-        "U5hbdsT9W7".to_string(),
-        InstalledGame {
-            id: "U5hbdsT9W7".to_string(),
-            biz_name: "nap_global".to_string(),
-            command_arguments: None,
-            command_wrapper: None,
-            environment: HashMap::new(),
-            executable_path: PathBuf::from("ZenlessZoneZero.exe"),
-            install_path: PathBuf::from("/path/to/Zenless Zone Zero/"),
-            runner: Runner::Wine(Wine {
-                version: "Spritz-Wine-TkG-10.15-3".to_string(),
-            }),
-            runtime_components: vec![RuntimeComponent::Dxvk("2.7.1".to_string())],
+    match &*startup.read() {
+        Ok((settings, recovery)) => rsx! {
+            Launcher {
+                loaded: settings.clone(),
+                recovery: recovery.clone(),
+            }
         },
-    );
+        Err(error) => rsx! {
+            StartupErrorPage {
+                error: error.clone(),
+                onretry,
+            }
+        },
+    }
+}
+
+/// `earlier` attempts that weren't tried again, followed by the `latest` ones.
+fn merge_attempts(earlier: &[PathAttempt], latest: Vec<PathAttempt>) -> Vec<PathAttempt> {
+    earlier
+        .iter()
+        .filter(|attempt| !latest.iter().any(|latest| latest.name == attempt.name))
+        .cloned()
+        .chain(latest)
+        .collect()
+}
+
+#[component]
+fn Launcher(loaded: GlobalSettings, recovery: Option<ConfigRecovery>) -> Element {
+    let mut settings = use_signal(|| loaded);
+    use_context_provider(move || Signal::new(recovery));
 
     to_owned![settings];
     use_drop(move || {
@@ -77,45 +169,94 @@ fn app() -> Element {
 
     use_context_provider(move || settings);
 
-    use_init_theme(|| DARK_THEME);
+    use_context_provider(move || DownloadManager::new(settings));
+    let installs = use_context_provider(Installs::new);
 
-    let _umu_handle = use_resource(|| async {
+    let _umu_handle = use_resource(move || async move {
         if let Err(e) = setup_umu().await {
             println!("Failed to set up umu-launcher: {e}");
         }
+
+        let cache_directory = settings.peek().cache_directory.clone();
+        if let Err(e) = umu_database::refresh(&cache_directory).await {
+            println!("{e}");
+        }
+    });
+
+    let mut cache_refresh = use_context_provider(|| Signal::new(CacheRefresh::default()));
+    let mut connectivity = use_context_provider(|| Signal::new(Connectivity::Online));
+
+    use_future(move || async move {
+        loop {
+            tokio::time::sleep(CONNECTIVITY_CHECK_INTERVAL).await;
+
+            if *connectivity.peek() == Connectivity::Offline && check_connectivity().await {
+                println!("Connection restored, reloading games..");
+                connectivity.set(Connectivity::Online);
+
+                let generation = cache_refresh.peek().generation;
+                cache_refresh.set(CacheRefresh {
+                    generation: generation + 1,
+                    bypass: true,
+                });
+            }
+        }
     });
+    let mut news: NewsStore = use_context_provider(|| Signal::new(HashMap::new()));
+
+    // Resetting `bypass` below must not trigger another rebuild
+    let generation = use_memo(move || cache_refresh().generation);
+    let hoyoplay = use_resource(move || async move {
+        generation();
+        let bypass = cache_refresh.peek().bypass;
+        let policy = if bypass {
+            CachePolicy::Bypass
+        } else {
+            CachePolicy::PreferCache
+        };
+
+        let response = get_games(policy).await;
+        if bypass {
+            cache_refresh.write().bypass = false;
+        }
+        // Pages load their content again, it may have been revalidated
+        news.write().clear();
 
-    let ctx = use_resource(move || async move {
-        let api_games = get_games()
-            .await
-            .map_err(|e| e.to_string())
-            .map(|v| v.games)
-            .unwrap_or_else(|e| {
+        match response {
+            Ok(response) => (response.games, None),
+            Err(e) => {
                 println!("Failed to load games from api: {e}");
-                Vec::new()
-            });
-
-        let mut api_news = HashMap::new();
-
-        for game in &api_games {
-            let id = game.id.to_owned();
-            let response = get_game_content(&id).await;
-
-            match response {
-                Ok(response) => {
-                    api_news.insert(id, response.content);
-                }
-                Err(e) => {
-                    println!("Failed to load game content: {e}");
-                }
+                (Vec::new(), Some(e))
             }
         }
+    });
+    let kuro_games = use_resource(move || async move {
+        generation();
+        kuro::get_games().await
+    });
 
-        Context {
-            api_games,
-            api_news,
+    // HoYoPlay games show up without waiting for Kuro
+    let mut ctx = use_signal(|| None::<Context>);
+    use_effect(move || {
+        if let Some((api_games, api_games_error)) = &*hoyoplay.read() {
+            let mut context = Context {
+                api_games: api_games.clone(),
+                api_games_error: api_games_error.clone(),
+                kuro_games: kuro_games.read().clone().unwrap_or_default(),
+                offline_games: HashMap::new(),
+            };
+            context.sync_summaries(&settings.peek());
+            ctx.set(Some(context));
         }
     });
     use_context_provider(move || ctx);
+
+    // Installs interrupted by the last exit continue once their game is listed
+    use_effect(move || {
+        if let Some(ctx) = &*ctx.read() {
+            installs.resume(ctx, settings);
+        }
+    });
+
     layout::app()
 }
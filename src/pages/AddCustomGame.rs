@@ -0,0 +1,192 @@
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use freya::prelude::*;
+use freya_router::prelude::*;
+
+use crate::{
+    components::MyButton,
+    layout::Route,
+    runners::{Runner, available_runners},
+    settings::{Channel, CustomGame, GlobalSettings, InstalledGame},
+};
+
+#[component]
+pub fn AddCustomGame() -> Element {
+    let mut settings = use_context::<Signal<GlobalSettings>>();
+    let navigator = use_navigator();
+
+    let mut name = use_signal(String::new);
+    let mut executable = use_signal(String::new);
+    let mut icon = use_signal(String::new);
+    let mut background = use_signal(String::new);
+    let mut error = use_signal(|| None::<String>);
+
+    // Discovery looks through the disk, once is enough
+    let runners = use_hook(|| available_runners(&settings.read().components_directory));
+    let mut runner = use_signal(|| Runner::detect(&settings.read().components_directory));
+
+    let onsave = move |_| {
+        let game = build_game(&name(), &executable(), &icon(), &background(), runner());
+        let (custom, installed) = match game {
+            Ok(game) => game,
+            Err(e) => {
+                error.set(Some(e));
+                return;
+            }
+        };
+
+        let id = installed.id.clone();
+        {
+            let mut settings = settings.write();
+            settings.custom_games.insert(id.clone(), custom);
+            settings.installed_games.insert(id.clone(), installed);
+            settings.save();
+        }
+
+        navigator.replace(Route::Game { game_id: id });
+    };
+
+    rsx! {
+        rect {
+            width: "fill",
+            height: "fill",
+            padding: "32 32 32 116",
+            spacing: "12",
+
+            label {
+                font_size: "32",
+                "Add a game"
+            }
+
+            label { "Name" }
+            Input {
+                value: name(),
+                placeholder: "Name",
+                onchange: move |value| name.set(value),
+            }
+
+            label { "Executable" }
+            Input {
+                value: executable(),
+                placeholder: "/path/to/game.exe",
+                onchange: move |value| executable.set(value),
+            }
+
+            label { "Icon (optional)" }
+            Input {
+                value: icon(),
+                placeholder: "/path/to/icon.png",
+                onchange: move |value| icon.set(value),
+            }
+
+            label { "Background (optional)" }
+            Input {
+                value: background(),
+                placeholder: "/path/to/background.png",
+                onchange: move |value| background.set(value),
+            }
+
+            label { "Runner" }
+            Dropdown {
+                value: runner.read().to_string(),
+                for option in runners {
+                    DropdownItem {
+                        value: option.to_string(),
+                        onpress: {
+                            to_owned![option];
+                            move |_| runner.set(option.clone())
+                        },
+                        label { "{option}" }
+                    }
+                }
+            }
+
+            if let Some(error) = &*error.read() {
+                label {
+                    color: "rgb(255, 100, 100)",
+                    "{error}"
+                }
+            }
+
+            MyButton {
+                onpress: onsave,
+                rect {
+                    font_size: "24",
+                    padding: "4 16",
+                    label { "Save" }
+                }
+            }
+        }
+    }
+}
+
+fn build_game(
+    name: &str,
+    executable: &str,
+    icon: &str,
+    background: &str,
+    runner: Runner,
+) -> Result<(CustomGame, InstalledGame), String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Name cannot be empty".to_string());
+    }
+
+    let executable = PathBuf::from(executable.trim());
+    if !executable.is_file() {
+        return Err(format!("Executable not found: {executable:?}"));
+    }
+    let (Some(install_path), Some(executable_path)) = (executable.parent(), executable.file_name())
+    else {
+        return Err(format!("Invalid executable path: {executable:?}"));
+    };
+
+    let optional_image = |path: &str| -> Result<Option<PathBuf>, String> {
+        let path = path.trim();
+        if path.is_empty() {
+            return Ok(None);
+        }
+
+        let path = PathBuf::from(path);
+        if !path.is_file() {
+            return Err(format!("Image not found: {path:?}"));
+        }
+
+        Ok(Some(path))
+    };
+
+    // Millisecond timestamps keep ids unique and sorted by creation time
+    let created = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| format!("Invalid system time: {e}"))?
+        .as_millis();
+    let id = format!("custom-{created}");
+
+    let custom = CustomGame {
+        name: name.to_string(),
+        icon: optional_image(icon)?,
+        background: optional_image(background)?,
+    };
+    let installed = InstalledGame {
+        id: id.clone(),
+        biz_name: id,
+        install_path: install_path.to_path_buf(),
+        executable_path: PathBuf::from(executable_path),
+        command_wrapper: None,
+        command_arguments: None,
+        environment: HashMap::new(),
+        runner,
+        runtime_components: Vec::new(),
+        version: None,
+        server: None,
+        channel: Channel::Official,
+        extras: HashMap::new(),
+        umu_id: None,
+    };
+
+    Ok((custom, installed))
+}
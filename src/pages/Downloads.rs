@@ -0,0 +1,272 @@
+use std::time::Duration;
+
+use freya::prelude::*;
+
+use crate::{
+    components::MyButton,
+    settings::GlobalSettings,
+    utils::download::{DownloadManager, DownloadState, DownloadTask, WaitReason, format_bytes},
+};
+
+const MIB: u64 = 1024 * 1024;
+
+#[component]
+pub fn Downloads() -> Element {
+    let manager = use_context::<DownloadManager>();
+    let mut settings = use_context::<Signal<GlobalSettings>>();
+    let tasks = manager.tasks.read().clone();
+
+    let mut limit = use_signal(|| {
+        settings
+            .peek()
+            .downloads
+            .bandwidth_limit
+            .map(|limit| (limit / MIB).to_string())
+            .unwrap_or_default()
+    });
+    let mut limit_error = use_signal(|| None::<String>);
+
+    // Applied on every change, running downloads pick it up right away
+    let onlimit = move |value: String| {
+        let parsed = match value.trim() {
+            "" => Ok(None),
+            value => value
+                .parse::<u64>()
+                .map(|limit| Some(limit * MIB))
+                .map_err(|_| format!("Not a number: {value}")),
+        };
+        limit.set(value);
+
+        match parsed {
+            Ok(parsed) => {
+                limit_error.set(None);
+                let mut settings = settings.write();
+                settings.downloads.bandwidth_limit = parsed;
+                settings.save();
+            }
+            Err(e) => limit_error.set(Some(e)),
+        }
+    };
+
+    rsx! {
+        rect {
+            width: "fill",
+            height: "fill",
+            padding: "32 32 32 116",
+            spacing: "12",
+
+            rect {
+                width: "fill",
+                direction: "horizontal",
+                cross_align: "center",
+                spacing: "12",
+                label {
+                    width: "fill",
+                    font_size: "32",
+                    "Downloads"
+                }
+                label { "Speed limit (MiB/s)" }
+                Input {
+                    value: limit(),
+                    placeholder: "Unlimited",
+                    onchange: onlimit,
+                }
+                MyButton {
+                    onpress: {
+                        to_owned![manager];
+                        move |_| manager.clear_completed()
+                    },
+                    rect {
+                        padding: "4 16",
+                        label { "Clear completed" }
+                    }
+                }
+            }
+
+            if let Some(error) = &*limit_error.read() {
+                label {
+                    color: "rgb(255, 100, 100)",
+                    "{error}"
+                }
+            }
+
+            if tasks.is_empty() {
+                label {
+                    color: "rgb(160, 160, 160)",
+                    "Nothing is being downloaded."
+                }
+            }
+
+            ScrollView {
+                rect {
+                    width: "fill",
+                    spacing: "8",
+                    for task in tasks {
+                        DownloadRow {
+                            key: "{task.id}",
+                            task: task.clone(),
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[component]
+fn DownloadRow(task: DownloadTask) -> Element {
+    let manager = use_context::<DownloadManager>();
+    let progress = task.progress.read().clone();
+    let id = task.id;
+
+    let size = match progress.total {
+        Some(total) => format!(
+            "{} / {}",
+            format_bytes(progress.downloaded),
+            format_bytes(total)
+        ),
+        None => format_bytes(progress.downloaded),
+    };
+    let percentage = match progress.unpacking {
+        Some((read, total)) => read as f32 / total.max(1) as f32 * 100.0,
+        None => progress
+            .total
+            .filter(|total| *total > 0)
+            .map(|total| progress.downloaded as f32 / total as f32 * 100.0)
+            .unwrap_or_default(),
+    };
+    let (status, color) = match &progress.state {
+        DownloadState::Completed if progress.unpacking.is_some() => {
+            (format!("Unpacking {percentage:.0}%"), "rgb(120, 200, 255)")
+        }
+        DownloadState::Queued => ("Queued".to_string(), "rgb(160, 160, 160)"),
+        DownloadState::Downloading => {
+            let eta = progress
+                .eta()
+                .map(|eta| format!(", {} left", format_duration(eta)))
+                .unwrap_or_default();
+            (
+                format!("{}/s{eta}", format_bytes(progress.speed)),
+                "rgb(120, 200, 255)",
+            )
+        }
+        DownloadState::Waiting(WaitReason::Window) => (
+            "Waiting for a download window".to_string(),
+            "rgb(160, 160, 160)",
+        ),
+        DownloadState::Waiting(WaitReason::GameRunning) => (
+            "Paused while a game is running".to_string(),
+            "rgb(160, 160, 160)",
+        ),
+        DownloadState::Paused => ("Paused".to_string(), "rgb(255, 180, 80)"),
+        DownloadState::Failed(e) => (format!("Failed: {e}"), "rgb(255, 100, 100)"),
+        DownloadState::Completed => ("Completed".to_string(), "rgb(120, 220, 120)"),
+    };
+    let can_pause = matches!(
+        progress.state,
+        DownloadState::Queued | DownloadState::Downloading | DownloadState::Waiting(_)
+    );
+    let can_resume = matches!(
+        progress.state,
+        DownloadState::Paused | DownloadState::Failed(_)
+    );
+    let cancel_label = if progress.state.is_completed() {
+        "Remove"
+    } else {
+        "Cancel"
+    };
+
+    rsx! {
+        rect {
+            width: "fill",
+            padding: "12",
+            corner_radius: "8",
+            background: "rgb(34, 34, 34, 0.6)",
+            spacing: "6",
+
+            rect {
+                width: "fill",
+                direction: "horizontal",
+                cross_align: "center",
+                spacing: "8",
+                label {
+                    width: "fill",
+                    font_size: "16",
+                    max_lines: "1",
+                    text_overflow: "ellipsis",
+                    "{task.name}"
+                }
+                if !progress.state.is_completed() {
+                    MyButton {
+                        onpress: {
+                            to_owned![manager];
+                            move |_| manager.reorder(id, -1)
+                        },
+                        label { "↑" }
+                    }
+                    MyButton {
+                        onpress: {
+                            to_owned![manager];
+                            move |_| manager.reorder(id, 1)
+                        },
+                        label { "↓" }
+                    }
+                }
+                if can_pause {
+                    MyButton {
+                        onpress: {
+                            to_owned![manager];
+                            move |_| manager.pause(id)
+                        },
+                        label { "Pause" }
+                    }
+                }
+                if can_resume {
+                    MyButton {
+                        onpress: {
+                            to_owned![manager];
+                            move |_| manager.resume(id)
+                        },
+                        label { "Resume" }
+                    }
+                }
+                MyButton {
+                    onpress: {
+                        to_owned![manager];
+                        move |_| manager.cancel(id)
+                    },
+                    label { "{cancel_label}" }
+                }
+            }
+
+            ProgressBar {
+                progress: percentage,
+            }
+
+            rect {
+                width: "fill",
+                direction: "horizontal",
+                label {
+                    width: "fill",
+                    font_size: "12",
+                    color: "{color}",
+                    "{status}"
+                }
+                label {
+                    font_size: "12",
+                    color: "rgb(160, 160, 160)",
+                    "{size}"
+                }
+            }
+        }
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    match seconds {
+        0..60 => format!("{seconds}s"),
+        60..3600 => format!("{}m {}s", seconds / 60, seconds % 60),
+        _ => format!("{}h {}m", seconds / 3600, seconds % 3600 / 60),
+    }
+}
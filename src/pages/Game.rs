@@ -1,46 +1,142 @@
 use freya::prelude::*;
+use freya_router::prelude::*;
 use reqwest::Url;
 
 use crate::{
     components::{MyButton, MyNetworkImage, MyNewsWidget},
-    context::Context,
+    context::{Context, NewsStore},
+    game_providers::hoyoplay::get_game_content,
+    layout::Route,
     settings::GlobalSettings,
+    utils::{
+        cache::CachePolicy,
+        installs::{InstallStage, Installs},
+    },
 };
 
 #[component]
 pub fn Game(game_id: String) -> Element {
-    let ctx = &use_context::<Context>();
-    let games = &ctx.api_games;
+    let ctx_signal = use_context::<Signal<Option<Context>>>();
+    let loaded = ctx_signal.read().is_some();
+    let ctx = &ctx_signal.read().clone().unwrap_or_default();
+    let settings = use_context::<Signal<GlobalSettings>>();
 
-    let Some(game) = games.iter().find(|g| g.id == game_id).cloned() else {
+    let mut news = use_context::<NewsStore>();
+    let installs = use_context::<Installs>();
+
+    let api_game = ctx.api_games.iter().find(|g| g.id == game_id).cloned();
+    let is_released = api_game
+        .as_ref()
+        .is_some_and(|game| game.display_status.is_released());
+
+    // Loaded when the page opens, and again after the store is cleared on a refresh
+    use_effect(use_reactive!(|(game_id, is_released)| {
+        if !is_released || news.read().contains_key(&game_id) {
+            return;
+        }
+
+        spawn(async move {
+            let response = get_game_content(&game_id, CachePolicy::PreferCache).await;
+            news.write()
+                .insert(game_id, response.map(|response| response.content));
+        });
+    }));
+
+    let kuro_game = ctx.kuro_games.iter().find(|g| g.id == game_id).cloned();
+    let custom_game = settings.read().custom_games.get(&game_id).cloned();
+    let list_error = ctx
+        .api_games_error
+        .as_ref()
+        .map(|e| format!("Cannot load the game list: {e}"));
+    let install_stage = installs.stage(&game_id);
+    let install_progress = install_stage.as_ref().and_then(progress_label);
+    let installing = install_progress.is_some();
+    let install_error = match &install_stage {
+        Some(InstallStage::Failed(e)) => Some(e.clone()),
+        _ => None,
+    };
+    let api_error = match news.read().get(&game_id) {
+        Some(Err(e)) => Some(e.to_string()),
+        // Likely missing from the list because it failed to load
+        _ if api_game.is_none() && kuro_game.is_none() && custom_game.is_none() => {
+            list_error.clone()
+        }
+        _ => None,
+    };
+    let background = if let Some(game) = &api_game {
+        Some(game.display.background.url.clone())
+    } else if let Some(game) = &kuro_game {
+        game.background
+            .as_ref()
+            .map(|background| background.background_file.clone())
+    } else if let Some(game) = &custom_game {
+        game.background
+            .as_ref()
+            .map(|background| Url::from_file_path(background).map(|url| url.to_string()))
+            .transpose()
+            .unwrap_or_default()
+    } else if settings.read().installed_games.contains_key(&game_id) {
+        // Installed but not listed right now, e.g. offline
+        ctx.offline_games
+            .get(&game_id)
+            .and_then(|summary| summary.background.clone())
+    } else if !loaded {
         return rsx! {
             rect {
                 label {
-                    "Game not found"
+                    "Loading..."
                 }
             }
         };
-    };
-
-    let Ok(url) = game.display.background.url.parse::<Url>() else {
+    } else {
         return rsx! {
             rect {
                 label {
-                    "Cannot parse background image URL"
+                    "Game not found"
+                }
+                if let Some(error) = list_error {
+                    label {
+                        "{error}"
+                    }
                 }
             }
         };
     };
 
-    let onpress = move |_| {
-        println!("Button Pressed!");
-        let ctx = &dioxus::hooks::use_context::<Signal<GlobalSettings>>();
-        let settings = &ctx.read();
-        let installed_games = &settings.installed_games;
-        if installed_games.contains_key(&game_id) {
-            let game = &installed_games[&game_id];
+    let background = match background.map(|url| url.parse::<Url>()).transpose() {
+        Ok(background) => background,
+        Err(_) => {
+            return rsx! {
+                rect {
+                    label {
+                        "Cannot parse background image URL"
+                    }
+                }
+            };
+        }
+    };
+
+    if let Some(game) = api_game
+        && !game.display_status.is_released()
+    {
+        return rsx! {
+            ComingSoon {
+                name: game.display.name,
+                background,
+                reservation: game.reservation.map(|reservation| reservation.link),
+            }
+        };
+    }
 
-            game.runner.run_game(game);
+    let onpress = {
+        to_owned![game_id];
+        move |_| {
+            let settings = &settings.read();
+            if let Some(game) = settings.installed_games.get(&game_id)
+                && let Err(e) = game.runner.run_game(game)
+            {
+                println!("{e}");
+            }
         }
     };
 
@@ -57,9 +153,11 @@ pub fn Game(game_id: String) -> Element {
                 height: "100%",
                 layer: "1",
 
-                MyNetworkImage {
-                    url: url,
-                    sampling: "catmull-rom",
+                if let Some(url) = background {
+                    MyNetworkImage {
+                        url: url,
+                        sampling: "catmull-rom",
+                    }
                 }
             },
 
@@ -77,8 +175,34 @@ pub fn Game(game_id: String) -> Element {
                     width: "500",
                     spacing: "32",
 
+                    if let Some(error) = api_error {
+                        rect {
+                            width: "100%",
+                            padding: "12",
+                            corner_radius: "16",
+                            background: "rgb(120, 30, 30, 0.8)",
+                            backdrop_blur: "16",
+                            label {
+                                "{error}"
+                            }
+                        }
+                    }
+
+                    if let Some(error) = install_error {
+                        rect {
+                            width: "100%",
+                            padding: "12",
+                            corner_radius: "16",
+                            background: "rgb(120, 30, 30, 0.8)",
+                            backdrop_blur: "16",
+                            label {
+                                "{error}"
+                            }
+                        }
+                    }
+
                     MyNewsWidget {
-                        game_id: game.id
+                        game_id: game_id.clone()
                     },
 
                     MyButton {
@@ -108,6 +232,53 @@ pub fn Game(game_id: String) -> Element {
                 spacing: "20",
                 padding: "32",
 
+                if installing {
+                    MyButton {
+                        onpress: {
+                            to_owned![game_id];
+                            move |_| installs.cancel(&game_id)
+                        },
+                        rect {
+                            font_size: "32",
+                            direction: "horizontal",
+                            cross_align: "center",
+                            spacing: "8",
+                            padding: "4",
+                            label { "Cancel" }
+                        }
+                    }
+                }
+
+                if is_released {
+                    HoYoPlayInstall { game_id: game_id.clone() }
+                }
+
+                if let Some(kuro_game) = kuro_game {
+                    MyButton {
+                        onpress: move |_| {
+                            if !installing {
+                                installs.kuro(kuro_game.clone(), settings);
+                            }
+                        },
+                        rect {
+                            font_size: "32",
+                            direction: "horizontal",
+                            cross_align: "center",
+                            spacing: "8",
+                            padding: "4",
+                            label {
+                                if let Some(progress) = &install_progress {
+                                    "{progress}"
+                                } else if settings.read().installed_games.contains_key(&game_id) {
+                                    "Update"
+                                } else {
+                                    "Install"
+                                }
+                            }
+                        }
+                    }
+                }
+
                 MyButton {
                     onpress: move |_| println!("Button Pressed!"),
                     rect {
@@ -132,6 +303,39 @@ pub fn Game(game_id: String) -> Element {
                 spacing: "20",
                 padding: "32",
 
+                if settings.read().installed_games.contains_key(&game_id) {
+                    Link {
+                        to: Route::GameSettings { game_id: game_id.clone() },
+                        MyButton {
+                            rect {
+                                font_size: "32",
+                                direction: "horizontal",
+                                cross_align: "center",
+                                spacing: "8",
+                                padding: "4",
+                                label { "Configure" }
+                            }
+                        }
+                    }
+                }
+
+                if custom_game.is_some() {
+                    MyButton {
+                        onpress: {
+                            to_owned![game_id];
+                            move |_| remove_custom_game(&game_id, settings)
+                        },
+                        rect {
+                            font_size: "32",
+                            direction: "horizontal",
+                            cross_align: "center",
+                            spacing: "8",
+                            padding: "4",
+                            label { "Remove" }
+                        }
+                    }
+                }
+
                 MyButton {
                     onpress: move |_| println!("Button Pressed!"),
                     rect {
@@ -147,3 +351,191 @@ pub fn Game(game_id: String) -> Element {
         }
     }
 }
+
+#[component]
+fn ComingSoon(name: String, background: Option<Url>, reservation: Option<String>) -> Element {
+    rsx! {
+        rect {
+            width: "fill",
+            height: "fill",
+
+            rect { // Background
+                position: "absolute",
+                position_top: "0",
+                position_left: "0",
+                width: "100%",
+                height: "100%",
+                layer: "1",
+
+                if let Some(url) = background {
+                    MyNetworkImage {
+                        url: url,
+                        sampling: "catmull-rom",
+                    }
+                }
+            },
+
+            rect { // Bottom Left
+                position: "absolute",
+                position_top: "0",
+                position_left: "96",
+                width: "100%",
+                height: "100%",
+                main_align: "end",
+                cross_align: "start",
+                padding: "32",
+                spacing: "16",
+
+                label {
+                    font_size: "40",
+                    "{name}"
+                }
+                label {
+                    font_size: "24",
+                    "Coming soon"
+                }
+
+                if let Some(link) = reservation {
+                    MyButton {
+                        onpress: move |_| {
+                            if let Err(e) = open::that(&link) {
+                                println!("Failed to open reservation link: {e}");
+                            }
+                        },
+                        rect {
+                            font_size: "24",
+                            direction: "horizontal",
+                            cross_align: "center",
+                            padding: "4",
+                            label { "Pre-register" }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[component]
+fn HoYoPlayInstall(game_id: String) -> Element {
+    let ctx = use_context::<Signal<Option<Context>>>();
+    let settings = use_context::<Signal<GlobalSettings>>();
+    let installs = use_context::<Installs>();
+    let mut selected_server = use_signal(|| None::<String>);
+
+    let game = ctx
+        .read()
+        .as_ref()
+        .and_then(|ctx| ctx.api_games.iter().find(|g| g.id == game_id).cloned());
+    let Some(game) = game else {
+        return rsx! {};
+    };
+
+    let servers = game
+        .game_server_configs
+        .iter()
+        .filter(|server| server.display_status.is_visible())
+        .cloned()
+        .collect::<Vec<_>>();
+    let installed = settings.read().installed_games.get(&game_id).cloned();
+
+    // Installs from before servers were remembered get theirs detected from the files
+    let server_id = installed
+        .as_ref()
+        .and_then(|installed| {
+            installed.server.clone().or_else(|| {
+                servers
+                    .iter()
+                    .find(|server| server.matches(&installed.install_path))
+                    .map(|server| server.game_id.clone())
+            })
+        })
+        .or_else(|| selected_server())
+        .or_else(|| servers.first().map(|server| server.game_id.clone()));
+    let server = servers
+        .iter()
+        .find(|server| Some(&server.game_id) == server_id.as_ref())
+        .cloned();
+
+    let progress = installs.stage(&game_id).as_ref().and_then(progress_label);
+    let installing = progress.is_some();
+    let onpress = {
+        to_owned![server];
+        move |_| {
+            if !installing {
+                installs.hoyoplay(game.clone(), server.clone(), settings);
+            }
+        }
+    };
+
+    // Switching the server of an install would need a reinstall, so it's only shown
+    let installed_server = installed
+        .as_ref()
+        .and(server.as_ref())
+        .map(|server| server.i18n_name.clone());
+
+    rsx! {
+        if let Some(name) = installed_server {
+            label {
+                font_size: "20",
+                "{name}"
+            }
+        } else if servers.len() > 1 {
+            Dropdown {
+                value: server.as_ref().map(|server| server.i18n_name.clone()).unwrap_or_default(),
+                for option in servers {
+                    DropdownItem {
+                        value: option.i18n_name.clone(),
+                        onpress: {
+                            to_owned![option];
+                            move |_| selected_server.set(Some(option.game_id.clone()))
+                        },
+                        label { "{option.i18n_name}" }
+                    }
+                }
+            }
+        }
+
+        MyButton {
+            onpress,
+            rect {
+                font_size: "32",
+                direction: "horizontal",
+                cross_align: "center",
+                spacing: "8",
+                padding: "4",
+                label {
+                    if let Some(progress) = progress {
+                        "{progress}"
+                    } else if installed.is_some() {
+                        "Update"
+                    } else {
+                        "Install"
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Label of the install buttons while the game installs.
+fn progress_label(stage: &InstallStage) -> Option<String> {
+    match stage {
+        InstallStage::Running => Some("Installing...".to_string()),
+        InstallStage::Unpacking { read, total } => {
+            Some(format!("Unpacking {}%", read * 100 / (*total).max(1)))
+        }
+        InstallStage::Failed(_) => None,
+    }
+}
+
+fn remove_custom_game(game_id: &str, mut settings: Signal<GlobalSettings>) {
+    {
+        let mut settings = settings.write();
+        settings.custom_games.remove(game_id);
+        settings.installed_games.remove(game_id);
+        settings.save();
+    }
+
+    navigator().replace(Route::Home);
+}
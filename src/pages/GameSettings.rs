@@ -0,0 +1,520 @@
+use std::{collections::HashSet, mem::discriminant, path::PathBuf};
+
+use freya::prelude::*;
+use freya_router::prelude::*;
+
+use crate::{
+    components::MyButton,
+    context::Context,
+    game_providers::hoyoplay::install::CHANNEL_SDK_ID,
+    layout::Route,
+    runners::{Runner, available_runners, installed_versions},
+    settings::{Channel, GlobalSettings, InstalledGame, RuntimeComponent},
+};
+
+/// Runtime components a game can enable, with the components subdirectory
+/// their builds are unpacked in.
+const RUNTIME_COMPONENTS: [(&str, &str, fn(String) -> RuntimeComponent); 3] = [
+    ("DXVK", "dxvk", RuntimeComponent::Dxvk),
+    (
+        "VKD3D-Proton",
+        "vkd3d-proton",
+        RuntimeComponent::Vkd3dProton,
+    ),
+    ("DXVK-NVAPI", "dxvk-nvapi", RuntimeComponent::DxvkNvApi),
+];
+
+/// Launch options being edited, kept as typed until they're saved.
+#[derive(Debug, Clone, PartialEq)]
+struct Draft {
+    runner: Runner,
+    runtime_components: Vec<RuntimeComponent>,
+    /// Rows of the editor, in the order they were added
+    environment: Vec<(String, String)>,
+    command_wrapper: String,
+    command_arguments: String,
+    install_path: String,
+    executable_path: String,
+    umu_id: String,
+    channel: Channel,
+}
+
+impl Draft {
+    fn from_game(game: &InstalledGame) -> Draft {
+        let mut environment = game
+            .environment
+            .iter()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect::<Vec<_>>();
+        environment.sort();
+
+        Draft {
+            runner: game.runner.clone(),
+            runtime_components: game.runtime_components.clone(),
+            environment,
+            command_wrapper: game.command_wrapper.clone().unwrap_or_default(),
+            command_arguments: game.command_arguments.clone().unwrap_or_default(),
+            install_path: game.install_path.to_string_lossy().into_owned(),
+            executable_path: game.executable_path.to_string_lossy().into_owned(),
+            umu_id: game.umu_id.clone().unwrap_or_default(),
+            channel: game.channel,
+        }
+    }
+
+    /// Everything that keeps the draft from being saved.
+    fn errors(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+
+        let install_path = PathBuf::from(self.install_path.trim());
+        if !install_path.is_dir() {
+            errors.push(format!("Install directory not found: {install_path:?}"));
+        } else {
+            let executable = install_path.join(self.executable_path.trim());
+            if self.executable_path.trim().is_empty() || !executable.is_file() {
+                errors.push(format!("Executable not found: {executable:?}"));
+            }
+        }
+
+        let mut keys = HashSet::new();
+        for (key, _) in &self.environment {
+            let key = key.trim();
+            if key.is_empty() {
+                errors.push("Environment variables need a name".to_string());
+            } else if key.contains(['=', ' ', '\t']) {
+                errors.push(format!("Invalid environment variable name: {key}"));
+            } else if !keys.insert(key) {
+                errors.push(format!("{key} is set twice"));
+            }
+        }
+
+        errors
+    }
+
+    fn apply(&self, game: &mut InstalledGame) {
+        let optional = |value: &str| {
+            let value = value.trim();
+            (!value.is_empty()).then(|| value.to_string())
+        };
+
+        game.runner = self.runner.clone();
+        game.runtime_components = self.runtime_components.clone();
+        game.environment = self
+            .environment
+            .iter()
+            .map(|(key, value)| (key.trim().to_string(), value.clone()))
+            .collect();
+        game.command_wrapper = optional(&self.command_wrapper);
+        game.command_arguments = optional(&self.command_arguments);
+        game.install_path = PathBuf::from(self.install_path.trim());
+        game.executable_path = PathBuf::from(self.executable_path.trim());
+        game.umu_id = optional(&self.umu_id);
+        if game.channel != self.channel {
+            // Installed again with the next update
+            game.extras.remove(CHANNEL_SDK_ID);
+            game.channel = self.channel;
+        }
+    }
+}
+
+/// What an edit changed, consecutive edits of the same field are undone at once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Runner,
+    Component(usize),
+    EnvironmentKey(usize),
+    EnvironmentValue(usize),
+    EnvironmentRows,
+    Wrapper,
+    Arguments,
+    InstallPath,
+    Executable,
+    UmuId,
+    Channel,
+}
+
+/// The draft and the versions it went through since the last save.
+#[derive(Clone, Copy, PartialEq)]
+struct Editor {
+    draft: Signal<Draft>,
+    history: Signal<Vec<(Field, Draft)>>,
+}
+
+impl Editor {
+    fn edit(mut self, field: Field, change: impl FnOnce(&mut Draft)) {
+        let previous = self.draft.peek().clone();
+        let same_field = self
+            .history
+            .peek()
+            .last()
+            .is_some_and(|(last, _)| *last == field);
+        if !same_field {
+            self.history.write().push((field, previous));
+        }
+
+        change(&mut self.draft.write());
+    }
+
+    fn undo(mut self) {
+        let previous = self.history.write().pop();
+        if let Some((_, previous)) = previous {
+            self.draft.set(previous);
+        }
+    }
+
+    fn reset(mut self, draft: Draft) {
+        self.draft.set(draft);
+        self.history.write().clear();
+    }
+}
+
+#[component]
+pub fn GameSettings(game_id: String) -> Element {
+    let settings = use_context::<Signal<GlobalSettings>>();
+
+    if !settings.read().installed_games.contains_key(&game_id) {
+        return rsx! {
+            rect {
+                padding: "32 32 32 116",
+                label { "This game is not installed" }
+            }
+        };
+    }
+
+    rsx! {
+        GameSettingsForm {
+            key: "{game_id}",
+            game_id: game_id.clone(),
+        }
+    }
+}
+
+/// Only rendered for installed games.
+#[component]
+fn GameSettingsForm(game_id: String) -> Element {
+    let mut settings = use_context::<Signal<GlobalSettings>>();
+    let ctx = use_context::<Signal<Option<Context>>>();
+    let saved = Draft::from_game(&settings.read().installed_games[&game_id]);
+
+    let editor = Editor {
+        draft: use_signal(|| saved.clone()),
+        history: use_signal(Vec::new),
+    };
+    // Discovery looks through the disk, once is enough
+    let (runners, component_versions) = use_hook(|| {
+        let components_directory = settings.peek().components_directory.clone();
+        let versions = RUNTIME_COMPONENTS
+            .iter()
+            .map(|(_, directory, _)| installed_versions(&components_directory.join(directory)))
+            .collect::<Vec<_>>();
+
+        (available_runners(&components_directory), versions)
+    });
+
+    // Only HoYoPlay games come in several channels
+    let has_channels = ctx
+        .read()
+        .as_ref()
+        .is_some_and(|ctx| ctx.api_games.iter().any(|game| game.id == game_id));
+
+    let draft = editor.draft.read().clone();
+    let errors = draft.errors();
+    let unsaved = draft != saved;
+    let can_undo = !editor.history.read().is_empty();
+
+    let mut runners = runners;
+    // A build that was removed since stays selectable
+    if !runners.contains(&draft.runner) {
+        runners.insert(0, draft.runner.clone());
+    }
+
+    let onsave = {
+        to_owned![game_id];
+        move |_| {
+            let draft = editor.draft.peek().clone();
+            if !draft.errors().is_empty() {
+                return;
+            }
+
+            let mut settings = settings.write();
+            if let Some(game) = settings.installed_games.get_mut(&game_id) {
+                draft.apply(game);
+                let saved = Draft::from_game(game);
+                settings.save();
+                editor.reset(saved);
+            }
+        }
+    };
+
+    rsx! {
+        rect {
+            width: "fill",
+            height: "fill",
+            padding: "32 32 32 116",
+            spacing: "12",
+
+            rect {
+                width: "fill",
+                direction: "horizontal",
+                cross_align: "center",
+                spacing: "8",
+                label {
+                    width: "fill",
+                    font_size: "32",
+                    "Game settings"
+                }
+                if unsaved {
+                    label {
+                        color: "rgb(255, 180, 80)",
+                        "Unsaved changes"
+                    }
+                }
+                MyButton {
+                    enabled: can_undo,
+                    onpress: move |_| editor.undo(),
+                    rect {
+                        padding: "4 16",
+                        label { "Undo" }
+                    }
+                }
+                MyButton {
+                    enabled: unsaved,
+                    onpress: {
+                        to_owned![saved];
+                        move |_| editor.reset(saved.clone())
+                    },
+                    rect {
+                        padding: "4 16",
+                        label { "Revert" }
+                    }
+                }
+                MyButton {
+                    enabled: unsaved && errors.is_empty(),
+                    onpress: onsave,
+                    rect {
+                        padding: "4 16",
+                        label { "Save" }
+                    }
+                }
+                Link {
+                    to: Route::Game { game_id: game_id.clone() },
+                    MyButton {
+                        rect {
+                            padding: "4 16",
+                            label { "Back" }
+                        }
+                    }
+                }
+            }
+
+            for error in errors {
+                label {
+                    font_size: "12",
+                    color: "rgb(255, 100, 100)",
+                    "{error}"
+                }
+            }
+
+            ScrollView {
+                rect {
+                    width: "fill",
+                    spacing: "8",
+
+                    label { "Runner" }
+                    Dropdown {
+                        value: draft.runner.to_string(),
+                        for option in runners {
+                            DropdownItem {
+                                value: option.to_string(),
+                                onpress: {
+                                    to_owned![option];
+                                    move |_| {
+                                        let option = option.clone();
+                                        editor.edit(Field::Runner, |draft| draft.runner = option)
+                                    }
+                                },
+                                label { "{option}" }
+                            }
+                        }
+                    }
+
+                    for (index, versions) in component_versions.into_iter().enumerate() {
+                        ComponentSetting {
+                            key: "{index}",
+                            editor,
+                            index,
+                            versions,
+                        }
+                    }
+
+                    label { "Environment variables" }
+                    for (index, (key, value)) in draft.environment.iter().cloned().enumerate() {
+                        rect {
+                            key: "{index}",
+                            direction: "horizontal",
+                            cross_align: "center",
+                            spacing: "8",
+                            Input {
+                                value: key,
+                                placeholder: "NAME",
+                                onchange: move |key| {
+                                    editor.edit(Field::EnvironmentKey(index), |draft| {
+                                        draft.environment[index].0 = key
+                                    })
+                                },
+                            }
+                            Input {
+                                width: "400",
+                                value: value,
+                                placeholder: "value",
+                                onchange: move |value| {
+                                    editor.edit(Field::EnvironmentValue(index), |draft| {
+                                        draft.environment[index].1 = value
+                                    })
+                                },
+                            }
+                            MyButton {
+                                onpress: move |_| {
+                                    editor.edit(Field::EnvironmentRows, |draft| {
+                                        draft.environment.remove(index);
+                                    })
+                                },
+                                label { "✕" }
+                            }
+                        }
+                    }
+                    MyButton {
+                        onpress: move |_| {
+                            editor.edit(Field::EnvironmentRows, |draft| {
+                                draft.environment.push((String::new(), String::new()))
+                            })
+                        },
+                        rect {
+                            padding: "4 16",
+                            label { "Add variable" }
+                        }
+                    }
+
+                    label { "Command wrapper" }
+                    Input {
+                        width: "500",
+                        value: draft.command_wrapper.clone(),
+                        placeholder: "e.g. gamemoderun",
+                        onchange: move |value| {
+                            editor.edit(Field::Wrapper, |draft| draft.command_wrapper = value)
+                        },
+                    }
+
+                    label { "Arguments" }
+                    Input {
+                        width: "500",
+                        value: draft.command_arguments.clone(),
+                        placeholder: "None",
+                        onchange: move |value| {
+                            editor.edit(Field::Arguments, |draft| draft.command_arguments = value)
+                        },
+                    }
+
+                    label { "Install directory" }
+                    Input {
+                        width: "500",
+                        value: draft.install_path.clone(),
+                        placeholder: "/path/to/game",
+                        onchange: move |value| {
+                            editor.edit(Field::InstallPath, |draft| draft.install_path = value)
+                        },
+                    }
+
+                    label { "Executable, relative to the install directory" }
+                    Input {
+                        width: "500",
+                        value: draft.executable_path.clone(),
+                        placeholder: "game.exe",
+                        onchange: move |value| {
+                            editor.edit(Field::Executable, |draft| draft.executable_path = value)
+                        },
+                    }
+
+                    if has_channels {
+                        label { "Channel" }
+                        Dropdown {
+                            value: draft.channel.to_string(),
+                            for option in Channel::ALL {
+                                DropdownItem {
+                                    value: option.to_string(),
+                                    onpress: move |_| {
+                                        editor.edit(Field::Channel, |draft| draft.channel = option)
+                                    },
+                                    label { "{option}" }
+                                }
+                            }
+                        }
+                    }
+
+                    label { "umu game id" }
+                    Input {
+                        width: "500",
+                        value: draft.umu_id.clone(),
+                        placeholder: "Looked up in the umu database",
+                        onchange: move |value| {
+                            editor.edit(Field::UmuId, |draft| draft.umu_id = value)
+                        },
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[component]
+fn ComponentSetting(editor: Editor, index: usize, versions: Vec<String>) -> Element {
+    let (name, _, make) = RUNTIME_COMPONENTS[index];
+    let kind = discriminant(&make(String::new()));
+    let current = editor
+        .draft
+        .read()
+        .runtime_components
+        .iter()
+        .find(|component| discriminant(*component) == kind)
+        .map(|component| component.version().to_string());
+
+    let mut versions = versions;
+    if let Some(current) = &current
+        && !versions.contains(current)
+    {
+        versions.insert(0, current.clone());
+    }
+
+    // Replaces the component of the same kind, or removes it
+    let select = move |version: Option<String>| {
+        editor.edit(Field::Component(index), |draft| {
+            draft
+                .runtime_components
+                .retain(|component| discriminant(component) != kind);
+            if let Some(version) = version {
+                draft.runtime_components.push(make(version));
+            }
+        })
+    };
+
+    rsx! {
+        label { "{name}" }
+        Dropdown {
+            value: current.clone().unwrap_or_else(|| "Disabled".to_string()),
+            DropdownItem {
+                value: "Disabled".to_string(),
+                onpress: move |_| select(None),
+                label { "Disabled" }
+            }
+            for version in versions {
+                DropdownItem {
+                    value: version.clone(),
+                    onpress: {
+                        to_owned![version];
+                        move |_| select(Some(version.clone()))
+                    },
+                    label { "{version}" }
+                }
+            }
+        }
+    }
+}
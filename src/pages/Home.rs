@@ -4,11 +4,17 @@ use freya::prelude::*;
 use freya_elements::attributes::font_style;
 use reqwest::Url;
 
-use crate::components::MyNetworkImage;
+use crate::{components::MyNetworkImage, context::Context};
 
 #[allow(non_snake_case)]
 #[component]
 pub fn Home() -> Element {
+    let ctx = use_context::<Signal<Option<Context>>>();
+    let list_error = ctx
+        .read()
+        .as_ref()
+        .and_then(|ctx| ctx.api_games_error.as_ref().map(|e| e.to_string()));
+
     let url = "https://cdn.discordapp.com/emojis/1136945196752453642.webp?size=32"
         .parse::<Url>()
         .unwrap();
@@ -61,6 +67,21 @@ pub fn Home() -> Element {
                 }
             },
 
+            if let Some(error) = list_error {
+                rect {
+                    position: "absolute",
+                    position_top: "16",
+                    position_left: "100",
+                    padding: "12",
+                    corner_radius: "16",
+                    background: "rgb(120, 30, 30, 0.8)",
+                    backdrop_blur: "16",
+                    label {
+                        "Cannot load the game list: {error}"
+                    }
+                }
+            }
+
             label {
                 font_family: "Noto Sans",
                 font_size: "20",
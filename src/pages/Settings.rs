@@ -0,0 +1,729 @@
+use std::path::PathBuf;
+
+use chrono::NaiveTime;
+use dioxus::prelude::{consume_context, spawn_forever};
+use freya::prelude::*;
+
+use crate::{
+    components::MyButton,
+    runners,
+    settings::{Directory, DownloadWindow, GlobalSettings},
+    utils::{
+        components::ComponentKind,
+        download::DownloadManager,
+        filesystem::{check_directory, move_directory},
+        installs::Installs,
+        releases::{RELEASE_KINDS, install_latest},
+        umu::{current_version, installed_versions, rollback_umu},
+    },
+};
+
+const MIB: u64 = 1024 * 1024;
+const GIB: u64 = 1024 * MIB;
+
+#[component]
+pub fn Settings() -> Element {
+    let mut settings = use_context::<Signal<GlobalSettings>>();
+    let pause_while_playing = settings.read().downloads.pause_while_playing;
+
+    // Every option is saved once it's confirmed and valid
+    let initial = use_hook(|| {
+        let settings = settings.peek();
+        let downloads = &settings.downloads;
+        let endpoints = &settings.endpoints;
+
+        InitialValues {
+            bandwidth_limit: downloads
+                .bandwidth_limit
+                .map(|limit| (limit / MIB).to_string())
+                .unwrap_or_default(),
+            large_download_size: (downloads.large_download_size / GIB).to_string(),
+            windows: format_windows(&downloads.windows),
+            umu_version: settings.umu_version.clone().unwrap_or_default(),
+            hoyoplay_api: endpoints.hoyoplay_api.clone().unwrap_or_default(),
+            hoyoplay_cdn: endpoints.hoyoplay_cdn.clone().unwrap_or_default(),
+            images: endpoints.images.clone().unwrap_or_default(),
+            kuro: endpoints.kuro.clone().unwrap_or_default(),
+        }
+    });
+
+    rsx! {
+        rect {
+            width: "fill",
+            height: "fill",
+            padding: "32 32 32 116",
+            spacing: "12",
+
+            label {
+                font_size: "32",
+                "Settings"
+            }
+
+            ScrollView {
+                rect {
+                    width: "fill",
+                    spacing: "12",
+
+                    SectionTitle { title: "Directories" }
+                    for directory in Directory::ALL {
+                        DirectorySetting {
+                            key: "{directory.label()}",
+                            directory,
+                        }
+                    }
+
+                    SectionTitle { title: "Downloads" }
+                    TextSetting {
+                        label: "Speed limit (MiB/s)",
+                        description: "Shared by all downloads.",
+                        placeholder: "Unlimited",
+                        initial: initial.bandwidth_limit,
+                        onchange: move |value: String| {
+                            let limit = parse_number(&value)?.map(|limit| limit * MIB);
+                            let mut settings = settings.write();
+                            settings.downloads.bandwidth_limit = limit;
+                            settings.save();
+                            Ok(())
+                        },
+                    }
+                    TextSetting {
+                        label: "Large download size (GiB)",
+                        description: "Downloads at least this big only run inside of the download windows.",
+                        placeholder: "1",
+                        initial: initial.large_download_size,
+                        onchange: move |value: String| {
+                            let size = parse_number(&value)?
+                                .filter(|size| *size > 0)
+                                .ok_or_else(|| "Must be at least 1".to_string())?;
+                            let mut settings = settings.write();
+                            settings.downloads.large_download_size = size * GIB;
+                            settings.save();
+                            Ok(())
+                        },
+                    }
+                    TextSetting {
+                        label: "Download windows",
+                        description: "Local times like 01:00-07:00, separated by commas. Any time when empty.",
+                        placeholder: "Any time",
+                        initial: initial.windows,
+                        onchange: move |value: String| {
+                            let windows = parse_windows(&value)?;
+                            let mut settings = settings.write();
+                            settings.downloads.windows = windows;
+                            settings.save();
+                            Ok(())
+                        },
+                    }
+                    rect {
+                        direction: "horizontal",
+                        cross_align: "center",
+                        spacing: "8",
+                        Switch {
+                            enabled: pause_while_playing,
+                            ontoggled: move |_| {
+                                let mut settings = settings.write();
+                                settings.downloads.pause_while_playing = !pause_while_playing;
+                                settings.save();
+                            },
+                        }
+                        label { "Pause downloads while a game is running" }
+                    }
+
+                    SectionTitle { title: "umu-launcher" }
+                    TextSetting {
+                        label: "Version",
+                        description: "Used from the next start. Follows the latest release when empty.",
+                        placeholder: "Latest",
+                        initial: initial.umu_version,
+                        onchange: move |value: String| {
+                            let version = value.trim();
+                            if version.contains(char::is_whitespace) {
+                                return Err(format!("Not a version: {version}"));
+                            }
+                            let mut settings = settings.write();
+                            settings.umu_version = (!version.is_empty()).then(|| version.to_string());
+                            settings.save();
+                            Ok(())
+                        },
+                    }
+                    UmuVersions {}
+
+                    SectionTitle { title: "Runners and components" }
+                    label {
+                        font_size: "12",
+                        color: "rgb(160, 160, 160)",
+                        "Downloaded from their releases, and only installed when they match the published checksum."
+                    }
+                    for kind in RELEASE_KINDS {
+                        ReleaseSetting {
+                            key: "{kind}",
+                            kind,
+                        }
+                    }
+
+                    SectionTitle { title: "Endpoints" }
+                    label {
+                        font_size: "12",
+                        color: "rgb(160, 160, 160)",
+                        "Replace the real services, e.g. with the mock server. Environment variables take precedence."
+                    }
+                    TextSetting {
+                        label: "HoYoPlay api",
+                        description: "ELYSIA_HOYOPLAY_API_URL",
+                        placeholder: "Default",
+                        initial: initial.hoyoplay_api,
+                        onchange: move |value: String| {
+                            let url = parse_url(&value)?;
+                            let mut settings = settings.write();
+                            settings.endpoints.hoyoplay_api = url;
+                            settings.save();
+                            Ok(())
+                        },
+                    }
+                    TextSetting {
+                        label: "HoYoPlay CDN",
+                        description: "ELYSIA_HOYOPLAY_CDN_URL",
+                        placeholder: "Default",
+                        initial: initial.hoyoplay_cdn,
+                        onchange: move |value: String| {
+                            let url = parse_url(&value)?;
+                            let mut settings = settings.write();
+                            settings.endpoints.hoyoplay_cdn = url;
+                            settings.save();
+                            Ok(())
+                        },
+                    }
+                    TextSetting {
+                        label: "Images",
+                        description: "ELYSIA_IMAGE_URL",
+                        placeholder: "Default",
+                        initial: initial.images,
+                        onchange: move |value: String| {
+                            let url = parse_url(&value)?;
+                            let mut settings = settings.write();
+                            settings.endpoints.images = url;
+                            settings.save();
+                            Ok(())
+                        },
+                    }
+                    TextSetting {
+                        label: "Kuro",
+                        description: "ELYSIA_KURO_URL",
+                        placeholder: "Default",
+                        initial: initial.kuro,
+                        onchange: move |value: String| {
+                            let url = parse_url(&value)?;
+                            let mut settings = settings.write();
+                            settings.endpoints.kuro = url;
+                            settings.save();
+                            Ok(())
+                        },
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The umu-launcher version in use, and a way back to the previous one when
+/// an update breaks games.
+#[component]
+fn UmuVersions() -> Element {
+    let settings = use_context::<Signal<GlobalSettings>>();
+    let mut status = use_signal(|| None::<Result<String, String>>);
+
+    // Read again after every rollback, `status` changes with it
+    let components_directory = settings.read().components_directory.clone();
+    let current = current_version(&components_directory);
+    let installed = installed_versions(&components_directory);
+    let can_roll_back = installed
+        .iter()
+        .any(|version| Some(version) != current.as_ref());
+
+    let current = current.unwrap_or_else(|| "None".to_string());
+    let installed = if installed.is_empty() {
+        "None".to_string()
+    } else {
+        installed.join(", ")
+    };
+
+    rsx! {
+        rect {
+            width: "fill",
+            spacing: "4",
+            label { "In use: {current}" }
+            label {
+                font_size: "12",
+                color: "rgb(160, 160, 160)",
+                "Installed: {installed}"
+            }
+            if can_roll_back {
+                MyButton {
+                    onpress: move |_| {
+                        let result = rollback_umu(&components_directory).map(|version| {
+                            format!("Now using {version}, set it as the version above to keep it after a restart.")
+                        });
+                        status.set(Some(result));
+                    },
+                    rect {
+                        padding: "4 16",
+                        label { "Roll back" }
+                    }
+                }
+            }
+            match &*status.read() {
+                Some(Ok(message)) => rsx! {
+                    label {
+                        font_size: "12",
+                        color: "rgb(120, 220, 120)",
+                        "{message}"
+                    }
+                },
+                Some(Err(e)) => rsx! {
+                    label {
+                        font_size: "12",
+                        color: "rgb(255, 100, 100)",
+                        "{e}"
+                    }
+                },
+                None => rsx! {},
+            }
+        }
+    }
+}
+
+/// Installed builds of a component, and a way to get the latest release.
+#[component]
+fn ReleaseSetting(kind: ComponentKind) -> Element {
+    let settings = use_context::<Signal<GlobalSettings>>();
+    let mut status = use_signal(|| None::<Result<String, String>>);
+    let mut installing = use_signal(|| false);
+
+    // Read again once an install finishes, `status` changes with it
+    let components_directory = settings.read().components_directory.clone();
+    let installed = runners::installed_versions(&components_directory.join(kind.to_string()));
+    let installed = if installed.is_empty() {
+        "None".to_string()
+    } else {
+        installed.join(", ")
+    };
+    let name = match kind {
+        ComponentKind::Umu => "umu-launcher",
+        ComponentKind::Proton => "Proton GE",
+        ComponentKind::Wine => "Wine GE",
+        ComponentKind::Dxvk => "DXVK",
+        ComponentKind::Vkd3dProton => "VKD3D-Proton",
+    };
+
+    let install = move |_| {
+        if *installing.peek() {
+            return;
+        }
+        installing.set(true);
+        status.set(None);
+        let (components_directory, temp_directory) = {
+            let settings = settings.peek();
+            (
+                settings.components_directory.clone(),
+                settings.temp_directory.clone(),
+            )
+        };
+        // Not tied to the page, leaving it mustn't stop an install halfway
+        spawn_forever(async move {
+            let result = install_latest(kind, &components_directory, &temp_directory)
+                .await
+                .map(|version| format!("{version} is installed"));
+            if let Ok(mut status) = status.try_write() {
+                *status = Some(result);
+            }
+            if let Ok(mut installing) = installing.try_write() {
+                *installing = false;
+            }
+        });
+    };
+
+    rsx! {
+        rect {
+            width: "fill",
+            spacing: "4",
+            label { "{name}" }
+            label {
+                font_size: "12",
+                color: "rgb(160, 160, 160)",
+                "Installed: {installed}"
+            }
+            MyButton {
+                enabled: !installing(),
+                onpress: install,
+                rect {
+                    padding: "4 16",
+                    label {
+                        if installing() {
+                            "Installing.."
+                        } else {
+                            "Install latest"
+                        }
+                    }
+                }
+            }
+            match &*status.read() {
+                Some(Ok(message)) => rsx! {
+                    label {
+                        font_size: "12",
+                        color: "rgb(120, 220, 120)",
+                        "{message}"
+                    }
+                },
+                Some(Err(e)) => rsx! {
+                    label {
+                        font_size: "12",
+                        color: "rgb(255, 100, 100)",
+                        "{e}"
+                    }
+                },
+                None => rsx! {},
+            }
+        }
+    }
+}
+
+/// Text of the inputs when the page is opened.
+#[derive(Clone)]
+struct InitialValues {
+    bandwidth_limit: String,
+    large_download_size: String,
+    windows: String,
+    umu_version: String,
+    hoyoplay_api: String,
+    hoyoplay_cdn: String,
+    images: String,
+    kuro: String,
+}
+
+#[component]
+fn SectionTitle(title: &'static str) -> Element {
+    rsx! {
+        label {
+            margin: "12 0 0 0",
+            font_size: "22",
+            "{title}"
+        }
+    }
+}
+
+/// An input that applies its value through `onchange` once it's confirmed
+/// with Enter or Apply, showing the error when it's rejected.
+#[component]
+fn TextSetting(
+    label: &'static str,
+    description: &'static str,
+    placeholder: &'static str,
+    initial: String,
+    onchange: Callback<String, Result<(), String>>,
+) -> Element {
+    let mut value = use_signal(|| initial.clone());
+    // Last value that was applied, nothing is saved while typing
+    let mut applied = use_signal(|| initial);
+    let mut error = use_signal(|| None::<String>);
+
+    let changed = *value.read() != *applied.read();
+    let mut apply = move || {
+        let new_value = value.peek().clone();
+        if *applied.peek() == new_value {
+            return;
+        }
+
+        let result = onchange.call(new_value.clone());
+        if result.is_ok() {
+            applied.set(new_value);
+        }
+        error.set(result.err());
+    };
+
+    rsx! {
+        rect {
+            width: "fill",
+            spacing: "4",
+            label { "{label}" }
+            label {
+                font_size: "12",
+                color: "rgb(160, 160, 160)",
+                "{description}"
+            }
+            rect {
+                direction: "horizontal",
+                cross_align: "center",
+                spacing: "8",
+                onkeydown: move |e: KeyboardEvent| {
+                    if e.key == Key::Enter {
+                        apply();
+                    }
+                },
+                Input {
+                    value: value(),
+                    placeholder: "{placeholder}",
+                    onchange: move |new_value: String| {
+                        value.set(new_value);
+                        error.set(None);
+                    },
+                }
+                MyButton {
+                    enabled: changed,
+                    onpress: move |_| apply(),
+                    rect {
+                        padding: "4 16",
+                        label { "Apply" }
+                    }
+                }
+            }
+            if let Some(error) = &*error.read() {
+                label {
+                    font_size: "12",
+                    color: "rgb(255, 100, 100)",
+                    "{error}"
+                }
+            }
+        }
+    }
+}
+
+#[component]
+fn DirectorySetting(directory: Directory) -> Element {
+    let settings = use_context::<Signal<GlobalSettings>>();
+    let installs = use_context::<Installs>();
+    let current = directory.path(&settings.read()).clone();
+
+    let mut draft = use_signal(|| current.to_string_lossy().into_owned());
+    // Outcome of the last change, as the success or error message
+    let mut outcome = use_signal(|| None::<Result<String, String>>);
+    let mut moving = use_signal(|| false);
+
+    let path = PathBuf::from(draft.read().trim());
+    let checked = check_directory(&path);
+    let changed = path != current;
+    let can_apply = changed && checked.is_ok();
+
+    let mut apply = move |path: PathBuf| {
+        if *moving.peek() {
+            return;
+        }
+        if let Err(e) = check_directory(&path) {
+            outcome.set(Some(Err(e)));
+            return;
+        }
+        // They'd keep writing to the old directory, or lose their files
+        let downloads = consume_context::<DownloadManager>();
+        if downloads.has_unfinished() || installs.any_running() {
+            outcome.set(Some(Err(
+                "Finish or cancel the downloads and installs first".to_string(),
+            )));
+            return;
+        }
+
+        moving.set(true);
+        // Not tied to the page, leaving it mustn't stop a move halfway
+        spawn_forever(async move {
+            let result = change_directory(settings, directory, path).await;
+            if let Ok(mut outcome) = outcome.try_write() {
+                *outcome = Some(result);
+            }
+            if let Ok(mut moving) = moving.try_write() {
+                *moving = false;
+            }
+        });
+    };
+
+    let browse = move |_| {
+        let start = directory.path(&settings.peek()).clone();
+        spawn(async move {
+            let picked = rfd::AsyncFileDialog::new()
+                .set_title(directory.label())
+                .set_directory(&start)
+                .pick_folder()
+                .await;
+            if let Some(folder) = picked {
+                let path = folder.path().to_path_buf();
+                draft.set(path.to_string_lossy().into_owned());
+                apply(path);
+            }
+        });
+    };
+
+    let feedback = if moving() {
+        Some(("Moving existing data..".to_string(), "rgb(160, 160, 160)"))
+    } else if let Some(Err(e)) = outcome.read().clone() {
+        Some((e, "rgb(255, 100, 100)"))
+    } else if changed {
+        Some(match &checked {
+            Ok(()) if directory.moves_data() => (
+                "Apply to move the existing data here".to_string(),
+                "rgb(160, 160, 160)",
+            ),
+            Ok(()) => (
+                "Apply to use this directory".to_string(),
+                "rgb(160, 160, 160)",
+            ),
+            Err(e) => (e.clone(), "rgb(255, 100, 100)"),
+        })
+    } else {
+        outcome.read().clone().map(|outcome| match outcome {
+            Ok(message) => (message, "rgb(120, 220, 120)"),
+            Err(e) => (e, "rgb(255, 100, 100)"),
+        })
+    };
+
+    rsx! {
+        rect {
+            width: "fill",
+            spacing: "4",
+            label { "{directory.label()}" }
+            label {
+                font_size: "12",
+                color: "rgb(160, 160, 160)",
+                "{directory.description()}"
+            }
+            rect {
+                width: "fill",
+                direction: "horizontal",
+                cross_align: "center",
+                spacing: "8",
+                onkeydown: {
+                    let path = path.clone();
+                    move |e: KeyboardEvent| {
+                        if e.key == Key::Enter && can_apply {
+                            apply(path.clone());
+                        }
+                    }
+                },
+                Input {
+                    width: "500",
+                    value: draft(),
+                    placeholder: "/path/to/directory",
+                    onchange: move |value| {
+                        draft.set(value);
+                        outcome.set(None);
+                    },
+                }
+                MyButton {
+                    enabled: !moving(),
+                    onpress: browse,
+                    rect {
+                        padding: "4 16",
+                        label { "Browse" }
+                    }
+                }
+                MyButton {
+                    enabled: can_apply && !moving(),
+                    onpress: move |_| apply(path.clone()),
+                    rect {
+                        padding: "4 16",
+                        label { "Apply" }
+                    }
+                }
+            }
+            if let Some((text, color)) = feedback {
+                label {
+                    font_size: "12",
+                    color: "{color}",
+                    "{text}"
+                }
+            }
+        }
+    }
+}
+
+/// Moves the data of `directory` to `path`, then switches to it.
+async fn change_directory(
+    mut settings: Signal<GlobalSettings>,
+    directory: Directory,
+    path: PathBuf,
+) -> Result<String, String> {
+    let current = directory.path(&settings.peek()).clone();
+    if directory.moves_data() {
+        let (from, to) = (current.clone(), path.clone());
+        tokio::task::spawn_blocking(move || move_directory(&from, &to))
+            .await
+            .map_err(|e| format!("Move thread failed: {e}"))??;
+    }
+
+    let mut settings = settings.write();
+    *directory.path_mut(&mut settings) = path.clone();
+    if directory == Directory::Games {
+        for game in settings.installed_games.values_mut() {
+            if let Ok(relative) = game.install_path.strip_prefix(&current) {
+                game.install_path = path.join(relative);
+            }
+        }
+    }
+    let problems = settings.validate().map_err(|e| e.to_string())?;
+    settings.save();
+
+    if problems.is_empty() {
+        Ok(format!("Now using {path:?}"))
+    } else {
+        Err(problems.join("\n"))
+    }
+}
+
+fn parse_number(value: &str) -> Result<Option<u64>, String> {
+    match value.trim() {
+        "" => Ok(None),
+        value => value
+            .parse::<u64>()
+            .map(Some)
+            .map_err(|_| format!("Not a number: {value}")),
+    }
+}
+
+fn parse_url(value: &str) -> Result<Option<String>, String> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Ok(None);
+    }
+
+    let url = value
+        .parse::<reqwest::Url>()
+        .map_err(|e| format!("Not a url: {e}"))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(format!("Unsupported scheme: {}", url.scheme()));
+    }
+
+    Ok(Some(value.to_string()))
+}
+
+fn parse_windows(value: &str) -> Result<Vec<DownloadWindow>, String> {
+    let parse_time = |time: &str| {
+        NaiveTime::parse_from_str(time.trim(), "%H:%M")
+            .map_err(|_| format!("Not a time: {}", time.trim()))
+    };
+
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|window| !window.is_empty())
+        .map(|window| {
+            let (start, end) = window
+                .split_once('-')
+                .ok_or_else(|| format!("Not a range like 01:00-07:00: {window}"))?;
+
+            Ok(DownloadWindow {
+                start: parse_time(start)?,
+                end: parse_time(end)?,
+            })
+        })
+        .collect()
+}
+
+fn format_windows(windows: &[DownloadWindow]) -> String {
+    windows
+        .iter()
+        .map(|window| {
+            format!(
+                "{}-{}",
+                window.start.format("%H:%M"),
+                window.end.format("%H:%M")
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
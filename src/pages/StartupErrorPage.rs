@@ -0,0 +1,157 @@
+use std::path::PathBuf;
+
+use freya::prelude::*;
+
+use crate::{
+    components::MyButton,
+    globals::PathAttempt,
+    settings::{Directory, UnusableDirectory},
+};
+
+/// Why the launcher can't start.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StartupError {
+    /// Every data directory that was tried
+    DataPath(Vec<PathAttempt>),
+    Settings(UnusableDirectory),
+}
+
+/// A directory picked on the startup error page.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChosenDirectory {
+    Data(PathBuf),
+    /// Replaces a directory of the config
+    Settings(Directory, PathBuf),
+}
+
+/// Shown instead of the launcher until starting works, the directory that
+/// can't be used can be picked here.
+#[component]
+pub fn StartupErrorPage(
+    error: StartupError,
+    onretry: EventHandler<Option<ChosenDirectory>>,
+) -> Element {
+    let mut chosen = use_signal(String::new);
+
+    let (title, attempts) = match &error {
+        StartupError::DataPath(attempts) => (
+            "No usable directory for data files was found".to_string(),
+            attempts
+                .iter()
+                .map(|attempt| (attempt.name.clone(), attempt.error.clone()))
+                .collect::<Vec<_>>(),
+        ),
+        StartupError::Settings(unusable) => (
+            format!(
+                "The {} directory can't be used",
+                unusable.directory.label().to_lowercase()
+            ),
+            vec![(
+                unusable.directory.label().to_string(),
+                unusable.error.clone(),
+            )],
+        ),
+    };
+    let (dialog_title, hint) = match &error {
+        StartupError::DataPath(_) => (
+            "Data directory",
+            "Use another data directory for this session, set ELYSIA_HOME to keep using it.",
+        ),
+        StartupError::Settings(unusable) => (
+            unusable.directory.label(),
+            "Use another directory, it's saved in the settings.",
+        ),
+    };
+    let directory = match &error {
+        StartupError::DataPath(_) => None,
+        StartupError::Settings(unusable) => Some(unusable.directory),
+    };
+
+    let browse = move |_| {
+        spawn(async move {
+            let picked = rfd::AsyncFileDialog::new()
+                .set_title(dialog_title)
+                .pick_folder()
+                .await;
+            if let Some(folder) = picked {
+                chosen.set(folder.path().to_string_lossy().into_owned());
+            }
+        });
+    };
+
+    rsx! {
+        rect {
+            width: "fill",
+            height: "fill",
+            padding: "48",
+            spacing: "12",
+            background: "rgb(24, 24, 24)",
+            color: "white",
+
+            label {
+                font_size: "32",
+                "{title}"
+            }
+
+            for (name, error) in attempts {
+                rect {
+                    width: "fill",
+                    padding: "12",
+                    corner_radius: "8",
+                    background: "rgb(120, 30, 30, 0.6)",
+                    spacing: "4",
+                    label {
+                        font_size: "16",
+                        "{name}"
+                    }
+                    label {
+                        font_size: "12",
+                        "{error}"
+                    }
+                }
+            }
+
+            label { "{hint}" }
+            rect {
+                direction: "horizontal",
+                cross_align: "center",
+                spacing: "8",
+                Input {
+                    width: "500",
+                    value: chosen(),
+                    placeholder: "/path/to/directory",
+                    onchange: move |value| chosen.set(value),
+                }
+                MyButton {
+                    onpress: browse,
+                    rect {
+                        padding: "4 16",
+                        label { "Browse" }
+                    }
+                }
+                MyButton {
+                    enabled: !chosen.read().trim().is_empty(),
+                    onpress: move |_| {
+                        let path = PathBuf::from(chosen.read().trim());
+                        onretry.call(Some(match directory {
+                            Some(directory) => ChosenDirectory::Settings(directory, path),
+                            None => ChosenDirectory::Data(path),
+                        }));
+                    },
+                    rect {
+                        padding: "4 16",
+                        label { "Use this directory" }
+                    }
+                }
+            }
+
+            MyButton {
+                onpress: move |_| onretry.call(None),
+                rect {
+                    padding: "4 16",
+                    label { "Retry" }
+                }
+            }
+        }
+    }
+}
@@ -1,10 +1,20 @@
 #![allow(non_snake_case)]
 #![allow(unused_imports)]
 
+mod AddCustomGame;
+mod Downloads;
 mod Error;
 mod Game;
+mod GameSettings;
 mod Home;
+mod Settings;
+mod StartupErrorPage;
 
+pub use AddCustomGame::*;
+pub use Downloads::*;
 pub use Error::*;
 pub use Game::*;
+pub use GameSettings::*;
 pub use Home::*;
+pub use Settings::*;
+pub use StartupErrorPage::*;
@@ -0,0 +1,189 @@
+//! Wine and Proton builds installed by other programs.
+//!
+//! Steam compatibility tools and Proton releases, Lutris runners and the
+//! system Wine are used in place, the games only remember their path.
+
+use std::{
+    collections::HashSet,
+    env, fs,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::OnceLock,
+};
+
+use super::{Proton, Runner, Wine};
+
+const SYSTEM_WINE: &str = "/usr/bin/wine";
+
+/// Scanned once per session, walking the Steam libraries and running
+/// `wine --version` is too slow to repeat whenever a page lists runners.
+static DISCOVERED: OnceLock<Vec<Runner>> = OnceLock::new();
+
+/// Every external build found, Proton first.
+pub fn discover_runners() -> Vec<Runner> {
+    DISCOVERED.get_or_init(scan_runners).clone()
+}
+
+fn scan_runners() -> Vec<Runner> {
+    let Ok(home) = env::var("HOME").map(PathBuf::from) else {
+        return system_wine().into_iter().collect();
+    };
+
+    let mut runners = Vec::new();
+    for root in steam_roots(&home) {
+        runners.extend(steam_compatibility_tools(&root));
+        for library in steam_libraries(&root) {
+            runners.extend(steam_protons(&library));
+        }
+    }
+
+    let lutris = home.join(".local/share/lutris/runners");
+    runners.extend(
+        builds(&lutris.join("proton"), "proton").map(|(version, path)| {
+            Runner::Proton(Proton {
+                version,
+                path: Some(path),
+            })
+        }),
+    );
+    runners.extend(
+        builds(&lutris.join("wine"), "bin/wine").map(|(version, path)| {
+            Runner::Wine(Wine {
+                version,
+                path: Some(path),
+            })
+        }),
+    );
+    runners.extend(system_wine());
+
+    runners
+}
+
+/// Steam installs, `~/.steam/root` and `~/.steam/steam` usually link to the
+/// same one.
+fn steam_roots(home: &Path) -> Vec<PathBuf> {
+    let mut seen = HashSet::new();
+
+    [
+        ".steam/root",
+        ".steam/steam",
+        ".local/share/Steam",
+        ".var/app/com.valvesoftware.Steam/.local/share/Steam",
+    ]
+    .into_iter()
+    .filter_map(|relative| fs::canonicalize(home.join(relative)).ok())
+    .filter(|root| seen.insert(root.clone()))
+    .collect()
+}
+
+/// The Steam install itself and the libraries added to it.
+fn steam_libraries(root: &Path) -> Vec<PathBuf> {
+    let mut libraries = vec![root.to_path_buf()];
+    if let Ok(text) = fs::read_to_string(root.join("steamapps/libraryfolders.vdf")) {
+        libraries.extend(
+            vdf_values(&text, "path")
+                .into_iter()
+                .map(PathBuf::from)
+                .filter_map(|library| fs::canonicalize(library).ok())
+                .filter(|library| library != root),
+        );
+    }
+
+    libraries
+}
+
+/// Builds like GE-Proton in `compatibilitytools.d`.
+fn steam_compatibility_tools(root: &Path) -> Vec<Runner> {
+    builds(&root.join("compatibilitytools.d"), "proton")
+        .map(|(name, path)| {
+            let version = read_version(&path)
+                .or_else(|| {
+                    fs::read_to_string(path.join("compatibilitytool.vdf"))
+                        .ok()
+                        .and_then(|text| vdf_values(&text, "display_name").into_iter().next())
+                })
+                .unwrap_or(name);
+
+            Runner::Proton(Proton {
+                version,
+                path: Some(path),
+            })
+        })
+        .collect()
+}
+
+/// Valve's Proton releases, installed like games.
+fn steam_protons(library: &Path) -> Vec<Runner> {
+    builds(&library.join("steamapps/common"), "proton")
+        .filter(|(name, _)| name.starts_with("Proton"))
+        .map(|(name, path)| {
+            Runner::Proton(Proton {
+                version: read_version(&path).unwrap_or(name),
+                path: Some(path),
+            })
+        })
+        .collect()
+}
+
+fn system_wine() -> Option<Runner> {
+    let wine = Path::new(SYSTEM_WINE);
+    if !wine.is_file() {
+        return None;
+    }
+
+    // Prints something like `wine-9.0`
+    let version = Command::new(wine)
+        .arg("--version")
+        .output()
+        .ok()
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .filter(|version| !version.is_empty())
+        .unwrap_or_else(|| "system".to_string());
+
+    Some(Runner::Wine(Wine {
+        version,
+        // `bin/wine` is looked up inside of it
+        path: wine.parent()?.parent().map(Path::to_path_buf),
+    }))
+}
+
+/// Subdirectories of `dir` containing `executable`, with their names, sorted.
+fn builds(dir: &Path, executable: &str) -> impl Iterator<Item = (String, PathBuf)> {
+    let mut builds = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.join(executable).is_file())
+        .filter_map(|path| {
+            let name = path.file_name()?.to_string_lossy().into_owned();
+            Some((name, path))
+        })
+        .collect::<Vec<_>>();
+    builds.sort();
+
+    builds.into_iter()
+}
+
+/// The `version` file of a Proton build holds a timestamp and the version,
+/// like `1718035392 GE-Proton9-7`.
+fn read_version(path: &Path) -> Option<String> {
+    let text = fs::read_to_string(path.join("version")).ok()?;
+
+    text.split_whitespace().last().map(str::to_string)
+}
+
+/// Values of `key` anywhere in a text VDF file, good enough for the flat
+/// lookups needed here.
+fn vdf_values(text: &str, key: &str) -> Vec<String> {
+    let key = format!("\"{key}\"");
+
+    text.lines()
+        .filter_map(|line| line.trim().strip_prefix(&key))
+        .filter_map(|rest| {
+            let value = rest.trim().strip_prefix('"')?;
+            let value = &value[..value.find('"')?];
+            Some(value.replace("\\\\", "\\"))
+        })
+        .collect()
+}
@@ -1,30 +1,295 @@
+mod discover;
 mod proton;
 mod wine;
 
+pub use discover::discover_runners;
 pub use proton::Proton;
 pub use wine::Wine;
 
+use std::{cmp::Ordering, ffi::OsString, fmt, fs, path::Path, process::Command, thread};
+
+use freya::prelude::{Readable, Signal};
 use serde::{Deserialize, Serialize};
 
-use crate::settings::InstalledGame;
+use crate::{
+    settings::{GlobalSettings, InstalledGame},
+    utils::components::{ComponentKind, verify_if_recorded},
+};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Runner {
     Native,
     Wine(Wine),
     Proton(Proton),
 }
 
+/// Program, arguments and environment a runner starts an executable with.
+pub struct Launch {
+    pub arguments: Vec<OsString>,
+    pub environment: Vec<(String, OsString)>,
+}
+
 impl Runner {
-    pub fn run_game(&self, game: &InstalledGame) {
+    /// Starts the game in its install directory, without waiting for it.
+    pub fn run_game(&self, game: &InstalledGame) -> Result<(), String> {
+        let arguments = game
+            .command_arguments
+            .as_deref()
+            .unwrap_or_default()
+            .split_whitespace()
+            .map(OsString::from)
+            .collect::<Vec<_>>();
+        let mut command = self.build(
+            game,
+            game.command_wrapper.as_deref().unwrap_or_default(),
+            &game.install_path.join(&game.executable_path),
+            &arguments,
+        )?;
+
+        let mut child = command
+            .spawn()
+            .map_err(|e| format!("Cannot start {}: {e}", game.biz_name))?;
+        let name = game.biz_name.clone();
+        // Reaped in the background so it doesn't linger as a zombie
+        thread::spawn(move || match child.wait() {
+            Ok(status) => println!("{name} exited with {status}"),
+            Err(e) => println!("Error when waiting for {name}: {e}"),
+        });
+
+        Ok(())
+    }
+
+    /// Runs `program` through this runner with the environment of `game`, for
+    /// installers and other helpers, so without its command wrapper.
+    pub fn command(
+        &self,
+        game: &InstalledGame,
+        program: &Path,
+        arguments: &[OsString],
+    ) -> Result<Command, String> {
+        self.build(game, "", program, arguments)
+    }
+
+    fn build(
+        &self,
+        game: &InstalledGame,
+        wrapper: &str,
+        program: &Path,
+        arguments: &[OsString],
+    ) -> Result<Command, String> {
+        // A build the launcher installed must still be the one it verified
+        if let Some((kind, version)) = self.installed_component() {
+            let settings = dioxus::prelude::consume_context::<Signal<GlobalSettings>>();
+            let components_directory = settings.read().components_directory.clone();
+            verify_if_recorded(&components_directory, kind, version)?;
+        }
+
+        let launch = match self {
+            Runner::Native => Launch {
+                arguments: vec![program.into()],
+                environment: Vec::new(),
+            },
+            Runner::Wine(wine) => wine.launch(game, program),
+            Runner::Proton(proton) => proton.launch(game, program),
+        };
+
+        let mut line = wrapper
+            .split_whitespace()
+            .map(OsString::from)
+            .chain(launch.arguments)
+            .chain(arguments.iter().cloned());
+        let program = line
+            .next()
+            .ok_or_else(|| format!("Nothing to run for {}", game.biz_name))?;
+
+        let mut command = Command::new(program);
+        command
+            .args(line)
+            .envs(launch.environment)
+            // Set by the user, so they win over the ones of the runner
+            .envs(&game.environment)
+            .current_dir(&game.install_path);
+
+        Ok(command)
+    }
+
+    /// Kind and version of a build in the components directory.
+    fn installed_component(&self) -> Option<(ComponentKind, &str)> {
         match self {
-            Runner::Native => {}
-            Runner::Wine(wine) => {
-                wine.run_game(game);
-            }
-            Runner::Proton(proton) => {
-                proton.run_game(game);
+            Runner::Wine(wine) if wine.path.is_none() => Some((ComponentKind::Wine, &wine.version)),
+            Runner::Proton(proton) if proton.path.is_none() => {
+                Some((ComponentKind::Proton, &proton.version))
             }
+            _ => None,
+        }
+    }
+}
+
+impl Runner {
+    /// Picks the newest installed Proton or Wine build for a newly installed
+    /// game, or the first one installed elsewhere when there's none.
+    pub fn detect(components_directory: &Path) -> Runner {
+        if let Some(version) = installed_versions(&components_directory.join("proton")).pop() {
+            return Runner::Proton(Proton {
+                version,
+                path: None,
+            });
+        }
+        if let Some(version) = installed_versions(&components_directory.join("wine")).pop() {
+            return Runner::Wine(Wine {
+                version,
+                path: None,
+            });
+        }
+
+        discover_runners()
+            .into_iter()
+            .next()
+            .unwrap_or(Runner::Native)
+    }
+}
+
+/// Every runner that can be used with the builds currently installed, ours
+/// first and then the ones found elsewhere.
+pub fn available_runners(components_directory: &Path) -> Vec<Runner> {
+    let proton = installed_versions(&components_directory.join("proton"))
+        .into_iter()
+        .map(|version| {
+            Runner::Proton(Proton {
+                version,
+                path: None,
+            })
+        });
+    let wine = installed_versions(&components_directory.join("wine"))
+        .into_iter()
+        .map(|version| {
+            Runner::Wine(Wine {
+                version,
+                path: None,
+            })
+        });
+
+    std::iter::once(Runner::Native)
+        .chain(proton)
+        .chain(wine)
+        .chain(discover_runners())
+        .collect()
+}
+
+impl fmt::Display for Runner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (name, version, path) = match self {
+            Runner::Native => return write!(f, "Native"),
+            Runner::Wine(wine) => ("Wine", &wine.version, &wine.path),
+            Runner::Proton(proton) => ("Proton", &proton.version, &proton.path),
+        };
+
+        match path {
+            // Builds found elsewhere can share a version with ours
+            Some(path) => write!(f, "{name} {version} ({})", path.display()),
+            None => write!(f, "{name} {version}"),
+        }
+    }
+}
+
+/// Lists the builds unpacked in a components subdirectory, oldest first.
+pub fn installed_versions(dir: &Path) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    let mut versions = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().is_dir())
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .collect::<Vec<_>>();
+    versions.sort_by(|a, b| compare_versions(a, b));
+
+    versions
+}
+
+/// Compares version names with their numbers as numbers, so that
+/// `GE-Proton10-9` comes before `GE-Proton10-15`.
+fn compare_versions(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a, b);
+
+    while !a.is_empty() && !b.is_empty() {
+        let (chunk_a, rest_a) = split_chunk(a);
+        let (chunk_b, rest_b) = split_chunk(b);
+
+        let numbers = chunk_a.parse::<u64>().ok().zip(chunk_b.parse::<u64>().ok());
+        let ordering = match numbers {
+            Some((number_a, number_b)) => number_a.cmp(&number_b),
+            None => chunk_a.cmp(chunk_b),
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+
+        (a, b) = (rest_a, rest_b);
+    }
+
+    a.len().cmp(&b.len())
+}
+
+/// The leading run of digits or of other characters, and the rest.
+fn split_chunk(text: &str) -> (&str, &str) {
+    let digits = text.starts_with(|c: char| c.is_ascii_digit());
+    let end = text
+        .find(|c: char| c.is_ascii_digit() != digits)
+        .unwrap_or(text.len());
+
+    text.split_at(end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sorts_versions_by_number() {
+        let mut versions = vec![
+            "GE-Proton10-15",
+            "GE-Proton9-27",
+            "GE-Proton10-9",
+            "GE-Proton10-1",
+        ];
+        versions.sort_by(|a, b| compare_versions(a, b));
+
+        assert_eq!(
+            versions,
+            [
+                "GE-Proton9-27",
+                "GE-Proton10-1",
+                "GE-Proton10-9",
+                "GE-Proton10-15",
+            ]
+        );
+    }
+
+    #[test]
+    fn sorts_dotted_versions() {
+        let mut versions = vec!["1.2.10", "1.2.9", "1.10.0", "1.2"];
+        versions.sort_by(|a, b| compare_versions(a, b));
+
+        assert_eq!(versions, ["1.2", "1.2.9", "1.2.10", "1.10.0"]);
+    }
+
+    #[test]
+    fn newest_installed_version_is_last() {
+        let dir = std::env::temp_dir().join(format!("elysia-versions-{}", std::process::id()));
+        for version in ["GE-Proton10-9", "GE-Proton10-15", "GE-Proton10-10"] {
+            fs::create_dir_all(dir.join(version)).unwrap();
         }
+        // Only directories are builds
+        fs::write(dir.join("GE-Proton10-99.tar.gz"), "").unwrap();
+
+        let versions = installed_versions(&dir);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            versions,
+            ["GE-Proton10-9", "GE-Proton10-10", "GE-Proton10-15"]
+        );
     }
 }
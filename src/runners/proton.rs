@@ -1,37 +1,57 @@
+use std::path::{Path, PathBuf};
+
 use freya::prelude::{Readable, Signal};
 use serde::{Deserialize, Serialize};
 
-use crate::settings::{GlobalSettings, InstalledGame};
+use super::Launch;
+use crate::{
+    settings::{GlobalSettings, InstalledGame},
+    utils::{
+        umu::umu_run_path,
+        umu_database::{self, DEFAULT_GAME_ID, NO_STORE},
+    },
+};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Proton {
     pub version: String,
+    /// Build outside of the components directory, like one installed by Steam
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
 }
 
 impl Proton {
-    pub fn run_game(&self, game: &InstalledGame) {
-        let ctx = &dioxus::hooks::use_context::<Signal<GlobalSettings>>();
+    /// Directory holding the `proton` script.
+    pub fn directory(&self, components_directory: &Path) -> PathBuf {
+        match &self.path {
+            Some(path) => path.clone(),
+            None => components_directory.join("proton").join(&self.version),
+        }
+    }
+
+    pub fn launch(&self, game: &InstalledGame, program: &Path) -> Launch {
+        let ctx = &dioxus::prelude::consume_context::<Signal<GlobalSettings>>();
         let settings = &ctx.read();
 
-        let components_path = settings.components_directory.join("proton");
-        let proton_path = components_path.join(&self.version);
-        
-        let umu_dir = settings.components_directory.join("umu-launcher");
-        let umu_run = umu_dir.join("umu-run");
-
-        let prefix = settings
-            .wineprefixes_directory
-            .join(&game.biz_name)
-            .to_string_lossy()
-            .into_owned();
-
-        println!(
-            "PROTONPATH=\"{}\" PREFIX=\"{}\" {:?} {:?}",
-            proton_path.display(),
-            prefix,
-            umu_run,
-            game.install_path.join(&game.executable_path)
-        );
+        let proton_path = self.directory(&settings.components_directory);
+        let umu_run = umu_run_path(&settings.components_directory);
+        let prefix = settings.wineprefixes_directory.join(&game.biz_name);
+
+        // Picks the protonfixes umu applies to the game
+        let database = umu_database::load_cached(&settings.cache_directory);
+        let (game_id, store) = match umu_database::resolve(&database, game) {
+            Some(umu_id) => (umu_id.game_id, umu_id.store),
+            None => (DEFAULT_GAME_ID.to_string(), NO_STORE.to_string()),
+        };
 
+        Launch {
+            arguments: vec![umu_run.into(), program.into()],
+            environment: vec![
+                ("PROTONPATH".to_string(), proton_path.into()),
+                ("WINEPREFIX".to_string(), prefix.into()),
+                ("GAMEID".to_string(), game_id.into()),
+                ("STORE".to_string(), store.into()),
+            ],
+        }
     }
 }
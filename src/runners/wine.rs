@@ -1,30 +1,39 @@
+use std::path::{Path, PathBuf};
+
 use freya::prelude::{Readable, Signal};
 use serde::{Deserialize, Serialize};
 
+use super::Launch;
 use crate::settings::{GlobalSettings, InstalledGame};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Wine {
     pub version: String,
+    /// Build outside of the components directory, like a system or Lutris Wine
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
 }
 
 impl Wine {
-    pub fn run_game(&self, game: &InstalledGame) {
-        let ctx = &dioxus::hooks::use_context::<Signal<GlobalSettings>>();
+    /// Directory holding `bin/wine`.
+    pub fn directory(&self, components_directory: &Path) -> PathBuf {
+        match &self.path {
+            Some(path) => path.clone(),
+            None => components_directory.join("wine").join(&self.version),
+        }
+    }
+
+    pub fn launch(&self, game: &InstalledGame, program: &Path) -> Launch {
+        let ctx = &dioxus::prelude::consume_context::<Signal<GlobalSettings>>();
         let settings = &ctx.read();
-        let components_path = &settings.components_directory.join("wine");
-        let exe = components_path.join(&self.version).join("bin/wine");
-        let prefix = &settings
-            .wineprefixes_directory
-            .join(&game.biz_name)
-            .to_string_lossy()
-            .into_owned();
+        let wine = self
+            .directory(&settings.components_directory)
+            .join("bin/wine");
+        let prefix = settings.wineprefixes_directory.join(&game.biz_name);
 
-        println!(
-            "WINEPREFIX=\"{}\" {:?} {:?}",
-            &prefix,
-            &exe,
-            &game.install_path.join(&game.executable_path)
-        )
+        Launch {
+            arguments: vec![wine.into(), program.into()],
+            environment: vec![("WINEPREFIX".to_string(), prefix.into())],
+        }
     }
 }
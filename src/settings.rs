@@ -1,67 +1,257 @@
 #![allow(dead_code)]
 
-use std::{collections::HashMap, fs, path::PathBuf};
+pub mod backups;
+pub mod migrations;
 
+use std::{
+    collections::HashMap,
+    env, fmt, fs,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use chrono::{Local, NaiveTime};
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    globals::{CONFIG_PATH, DATA_PATH},
-    runners::Runner,
-    utils::filesystem::ensure_or_default,
+    globals::{from_stored_path, paths, to_stored_path},
+    runners::{Proton, Runner, Wine},
+    utils::filesystem::{ensure_dir, ensure_writable, write_atomic},
 };
+use migrations::{CURRENT_SCHEMA_VERSION, LegacyPaths};
+
+/// Set while saving would overwrite a config that has to be kept, see
+/// `block_saving`
+static SAVING_BLOCKED: AtomicBool = AtomicBool::new(false);
+
+/// Makes `GlobalSettings::save` do nothing, e.g. until the user decides what
+/// happens to a broken config that couldn't be moved.
+pub fn block_saving(blocked: bool) {
+    SAVING_BLOCKED.store(blocked, Ordering::Relaxed);
+}
+
+/// Why the saved config couldn't be loaded.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LoadError {
+    /// Written by a newer version of the launcher, it's left as it is
+    Newer(String),
+    Unreadable(String),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Newer(e) | LoadError::Unreadable(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl From<String> for LoadError {
+    fn from(error: String) -> Self {
+        LoadError::Unreadable(error)
+    }
+}
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(default)]
 pub struct GlobalSettings {
+    /// Schema the config was written with, see `migrations`
+    pub schema_version: u32,
+
     pub wineprefixes_directory: PathBuf,
     pub components_directory: PathBuf,
     pub temp_directory: PathBuf,
     pub cache_directory: PathBuf,
+    pub games_directory: PathBuf,
+
+    pub endpoints: Endpoints,
+    /// umu-launcher version to use, the latest release when unset
+    pub umu_version: Option<String>,
+    pub downloads: DownloadSettings,
 
     pub installed_games: HashMap<String, InstalledGame>,
+    pub custom_games: HashMap<String, CustomGame>,
 }
 
 impl Default for GlobalSettings {
     fn default() -> Self {
-        let data_path = &*DATA_PATH;
-        let wineprefixes_directory = data_path.join("wineprefixes/");
-        let components_directory = data_path.join("components/");
-        let temp_directory = data_path.join("temp/");
-        let cache_directory = data_path.join("cache/");
+        // Empty before the paths are resolved, `validate` refuses them
+        let default_path = |directory: Directory| directory.default_path().unwrap_or_default();
 
         Self {
-            wineprefixes_directory,
-            components_directory,
-            temp_directory,
-            cache_directory,
+            schema_version: CURRENT_SCHEMA_VERSION,
+            wineprefixes_directory: default_path(Directory::Wineprefixes),
+            components_directory: default_path(Directory::Components),
+            temp_directory: default_path(Directory::Temp),
+            cache_directory: default_path(Directory::Cache),
+            games_directory: default_path(Directory::Games),
+            endpoints: Endpoints::default(),
+            umu_version: None,
+            downloads: DownloadSettings::default(),
             installed_games: HashMap::new(),
+            custom_games: HashMap::new(),
+        }
+    }
+}
+
+/// A directory of the config that can be moved from the settings page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Directory {
+    Games,
+    Wineprefixes,
+    Components,
+    Cache,
+    Temp,
+}
+
+impl Directory {
+    pub const ALL: [Directory; 5] = [
+        Directory::Games,
+        Directory::Wineprefixes,
+        Directory::Components,
+        Directory::Cache,
+        Directory::Temp,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Directory::Games => "Games",
+            Directory::Wineprefixes => "Wine prefixes",
+            Directory::Components => "Runners and components",
+            Directory::Cache => "Cache",
+            Directory::Temp => "Temporary files",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Directory::Games => "New games are installed here, games inside of it move with it.",
+            Directory::Wineprefixes => "One prefix per game, moved with their saves and settings.",
+            Directory::Components => "Wine, Proton, DXVK and umu-launcher builds.",
+            Directory::Cache => "Api responses and images, safe to delete.",
+            Directory::Temp => "Downloads before they're unpacked, nothing is moved.",
+        }
+    }
+
+    pub fn path(self, settings: &GlobalSettings) -> &PathBuf {
+        match self {
+            Directory::Games => &settings.games_directory,
+            Directory::Wineprefixes => &settings.wineprefixes_directory,
+            Directory::Components => &settings.components_directory,
+            Directory::Cache => &settings.cache_directory,
+            Directory::Temp => &settings.temp_directory,
+        }
+    }
+
+    pub fn path_mut(self, settings: &mut GlobalSettings) -> &mut PathBuf {
+        match self {
+            Directory::Games => &mut settings.games_directory,
+            Directory::Wineprefixes => &mut settings.wineprefixes_directory,
+            Directory::Components => &mut settings.components_directory,
+            Directory::Cache => &mut settings.cache_directory,
+            Directory::Temp => &mut settings.temp_directory,
         }
     }
+
+    /// Where it is unless the config says otherwise.
+    pub fn default_path(self) -> Result<PathBuf, String> {
+        let paths = paths()?;
+        Ok(match self {
+            Directory::Games => paths.data.join("games"),
+            Directory::Wineprefixes => paths.data.join("wineprefixes"),
+            Directory::Components => paths.data.join("components"),
+            Directory::Cache => paths.cache,
+            Directory::Temp => paths.temp,
+        })
+    }
+
+    /// Temporary files only matter while something is being installed
+    pub fn moves_data(self) -> bool {
+        self != Directory::Temp
+    }
+}
+
+/// A directory that can't be used, not even at its default.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnusableDirectory {
+    pub directory: Directory,
+    pub error: String,
+}
+
+impl fmt::Display for UnusableDirectory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.directory.label(), self.error)
+    }
 }
 
 impl GlobalSettings {
-    pub fn load() -> Result<GlobalSettings, String> {
-        let config_path = &*CONFIG_PATH;
+    /// The saved config, `None` when there's none yet.
+    pub fn load() -> Result<Option<GlobalSettings>, LoadError> {
+        let config_path = &paths()?.config_file();
         let exists =
             fs::exists(config_path).map_err(|e| format!("Cannot check if config exists: {e}"))?;
         if !exists {
-            return Err("Config does not exist".to_string());
+            return Ok(None);
+        }
+
+        Self::load_from(config_path).map(Some)
+    }
+
+    /// Replaces the config with a backup, even while saving is blocked.
+    pub fn restore(backup: &Path) -> Result<GlobalSettings, String> {
+        let mut settings = Self::load_from(backup).map_err(|e| e.to_string())?;
+        settings.validate().map_err(|e| e.to_string())?;
+        block_saving(false);
+        settings.save();
+
+        Ok(settings)
+    }
+
+    /// Reads and migrates a config, and backs it up once it loaded fine.
+    fn load_from(path: &Path) -> Result<GlobalSettings, LoadError> {
+        let data = fs::read(path).map_err(|e| format!("Cannot read config file: {e}"))?;
+        let mut config = serde_json::from_slice::<serde_json::Value>(&data)
+            .map_err(|e| format!("Cannot parse saved config: {e}"))?;
+
+        let version = migrations::schema_version(&config)?;
+        if version > CURRENT_SCHEMA_VERSION {
+            return Err(LoadError::Newer(format!(
+                "Config schema {version} is newer than the supported {CURRENT_SCHEMA_VERSION}, it was written by a newer version of the launcher"
+            )));
         }
+        if version < CURRENT_SCHEMA_VERSION {
+            // Older versions of the launcher can keep using their copy
+            let backup = paths()?.config.join(format!("config.v{version}.json"));
+            write_atomic(&backup, &data)?;
+        }
+        migrations::migrate(&mut config, &LegacyPaths::current()?)?;
 
-        let data = fs::read(config_path).map_err(|e| format!("Cannot read config file: {e}"))?;
-        let mut settings = serde_json::from_slice::<GlobalSettings>(&data)
+        let mut settings = serde_json::from_value::<GlobalSettings>(config)
             .map_err(|e| format!("Cannot deserialize saved config: {e}"))?;
+        // Portable configs are relative to their data directory
+        settings.map_paths(from_stored_path);
 
-        settings.validate();
+        if let Err(e) = backups::backup(&data) {
+            println!("Cannot back up config: {e}");
+        }
 
         Ok(settings)
     }
 
     pub fn save(&self) {
-        let data = serde_json::to_vec_pretty(self);
+        if SAVING_BLOCKED.load(Ordering::Relaxed) {
+            println!("Not saving the config, the saved one has to be kept");
+            return;
+        }
+
+        // Lets the portable home be moved somewhere else
+        let mut stored = self.clone();
+        stored.map_paths(to_stored_path);
+        let data = serde_json::to_vec_pretty(&stored);
         match data {
             Ok(data) => {
-                if let Err(e) = fs::write(&*CONFIG_PATH, data) {
+                if let Err(e) = paths().and_then(|paths| write_atomic(&paths.config_file(), &data))
+                {
                     println!("Error when writing config file: {e}");
                 }
             }
@@ -71,31 +261,80 @@ impl GlobalSettings {
         }
     }
 
-    pub fn validate(&mut self) {
-        let mut check_fn = || -> Result<(), String> {
-            self.wineprefixes_directory = ensure_or_default(
-                &self.wineprefixes_directory,
-                &DATA_PATH.join("wineprefixes"),
-            )?
-            .to_path_buf();
-            self.components_directory =
-                ensure_or_default(&self.components_directory, &DATA_PATH.join("components"))?
-                    .to_path_buf();
-            self.temp_directory =
-                ensure_or_default(&self.temp_directory, &DATA_PATH.join("temp"))?.to_path_buf();
-            self.cache_directory =
-                ensure_or_default(&self.cache_directory, &DATA_PATH.join("cache"))?.to_path_buf();
+    /// Replaces every path of the config, except the ones relative to a game.
+    fn map_paths(&mut self, map: impl Fn(&Path) -> PathBuf) {
+        for directory in [
+            &mut self.wineprefixes_directory,
+            &mut self.components_directory,
+            &mut self.temp_directory,
+            &mut self.cache_directory,
+            &mut self.games_directory,
+        ] {
+            *directory = map(directory);
+        }
 
-            Ok(())
-        };
+        for game in self.installed_games.values_mut() {
+            game.install_path = map(&game.install_path);
+            if let Runner::Wine(Wine {
+                path: Some(path), ..
+            })
+            | Runner::Proton(Proton {
+                path: Some(path), ..
+            }) = &mut game.runner
+            {
+                *path = map(path);
+            }
+        }
 
-        if let Err(e) = check_fn() {
-            panic!("Cannot use current or default path: {e}");
+        for game in self.custom_games.values_mut() {
+            for image in [&mut game.icon, &mut game.background].into_iter().flatten() {
+                *image = map(image);
+            }
         }
     }
+
+    /// Ids of manually added games in the order they were created.
+    pub fn custom_game_ids(&self) -> Vec<String> {
+        let mut ids = self.custom_games.keys().cloned().collect::<Vec<_>>();
+        ids.sort();
+
+        ids
+    }
+
+    /// Replaces every directory that can't be used with its default, and
+    /// describes each replacement. Fails when a default can't be used either.
+    pub fn validate(&mut self) -> Result<Vec<String>, UnusableDirectory> {
+        let mut problems = Vec::new();
+
+        for directory in Directory::ALL {
+            let path = directory.path_mut(self);
+            let Err(e) = ensure_dir(path).and_then(|()| ensure_writable(path)) else {
+                continue;
+            };
+
+            let default = directory
+                .default_path()
+                .and_then(|default| {
+                    ensure_dir(&default)?;
+                    ensure_writable(&default)?;
+
+                    Ok(default)
+                })
+                .map_err(|error| UnusableDirectory {
+                    directory,
+                    error: format!("Cannot use {path:?} or its default: {error}"),
+                })?;
+            let problem = format!("{e}, using {default:?} instead");
+            println!("Error when creating/loading directory from config: {problem}");
+            problems.push(problem);
+            *path = default;
+        }
+
+        Ok(problems)
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct InstalledGame {
     pub id: String,
     pub biz_name: String,
@@ -106,13 +345,237 @@ pub struct InstalledGame {
     pub environment: HashMap<String, String>,
     pub runner: Runner,
     pub runtime_components: Vec<RuntimeComponent>,
+    /// Installed version, when the provider tells us one
+    #[serde(default)]
+    pub version: Option<String>,
+    /// Game id of the server config the game was installed from
+    #[serde(default)]
+    pub server: Option<String>,
+    /// Distribution channel, decides which channel SDK gets installed
+    #[serde(default)]
+    pub channel: Channel,
+    /// Installed versions of channel SDKs and plugins, keyed by package id
+    #[serde(default)]
+    pub extras: HashMap<String, String>,
+    /// umu game id to use instead of the one looked up in the umu database
+    #[serde(default)]
+    pub umu_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Channel {
+    #[default]
+    Official,
+    Bilibili,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl Channel {
+    pub const ALL: [Channel; 2] = [Channel::Official, Channel::Bilibili];
+}
+
+impl fmt::Display for Channel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Channel::Official => write!(f, "Official"),
+            Channel::Bilibili => write!(f, "Bilibili"),
+        }
+    }
+}
+
+/// Base URLs replacing the real services, e.g. to run against the local mock
+/// server. Environment variables take precedence over the config file.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Endpoints {
+    /// `ELYSIA_HOYOPLAY_API_URL`, replaces the whole hyp-connect api base
+    pub hoyoplay_api: Option<String>,
+    /// `ELYSIA_HOYOPLAY_CDN_URL`, replaces the host of package downloads
+    pub hoyoplay_cdn: Option<String>,
+    /// `ELYSIA_IMAGE_URL`, replaces the host of every remote image
+    pub images: Option<String>,
+    /// `ELYSIA_KURO_URL`, replaces both the Kuro launcher api and its CDN
+    pub kuro: Option<String>,
+}
+
+impl Endpoints {
+    pub fn hoyoplay_api(&self) -> Option<String> {
+        Self::resolve("ELYSIA_HOYOPLAY_API_URL", &self.hoyoplay_api)
+    }
+
+    pub fn hoyoplay_cdn(&self) -> Option<String> {
+        Self::resolve("ELYSIA_HOYOPLAY_CDN_URL", &self.hoyoplay_cdn)
+    }
+
+    pub fn images(&self) -> Option<String> {
+        Self::resolve("ELYSIA_IMAGE_URL", &self.images)
+    }
+
+    pub fn kuro(&self) -> Option<String> {
+        Self::resolve("ELYSIA_KURO_URL", &self.kuro)
+    }
+
+    fn resolve(var: &str, configured: &Option<String>) -> Option<String> {
+        env::var(var)
+            .ok()
+            .or_else(|| configured.clone())
+            .filter(|url| !url.is_empty())
+    }
+
+    /// Moves `url` to another host, keeping its path and query.
+    pub fn rewrite(url: &str, base: Option<String>) -> String {
+        let Some(base) = base else {
+            return url.to_string();
+        };
+        let Ok(parsed) = url.parse::<reqwest::Url>() else {
+            return url.to_string();
+        };
+
+        let mut rewritten = format!("{}{}", base.trim_end_matches('/'), parsed.path());
+        if let Some(query) = parsed.query() {
+            rewritten.push('?');
+            rewritten.push_str(query);
+        }
+
+        rewritten
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DownloadSettings {
+    /// Bytes per second shared by all downloads, unlimited when unset
+    pub bandwidth_limit: Option<u64>,
+    /// Local times large downloads are limited to, any time when empty
+    pub windows: Vec<DownloadWindow>,
+    /// Downloads at least this many bytes count as large
+    pub large_download_size: u64,
+    /// Pause downloads while an installed game is running
+    pub pause_while_playing: bool,
+}
+
+impl Default for DownloadSettings {
+    fn default() -> Self {
+        Self {
+            bandwidth_limit: None,
+            windows: Vec::new(),
+            large_download_size: 1024 * 1024 * 1024,
+            pause_while_playing: false,
+        }
+    }
+}
+
+impl DownloadSettings {
+    /// Whether a download of `size` bytes may run right now.
+    pub fn allows(&self, size: Option<u64>) -> bool {
+        let large = size.is_some_and(|size| size >= self.large_download_size);
+        if !large || self.windows.is_empty() {
+            return true;
+        }
+
+        let now = Local::now().time();
+        self.windows.iter().any(|window| window.contains(now))
+    }
+}
+
+/// A daily time range, e.g. `{ "start": "01:00:00", "end": "07:00:00" }`. It
+/// may wrap around midnight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DownloadWindow {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl DownloadWindow {
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+/// Display data for a game added by hand, its launch options live in the
+/// matching `InstalledGame`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CustomGame {
+    pub name: String,
+    pub icon: Option<PathBuf>,
+    pub background: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum RuntimeComponent {
     Dxvk(ComponentVersion),
     Vkd3dProton(ComponentVersion),
     DxvkNvApi(ComponentVersion),
 }
 
+impl RuntimeComponent {
+    pub fn version(&self) -> &str {
+        match self {
+            RuntimeComponent::Dxvk(version)
+            | RuntimeComponent::Vkd3dProton(version)
+            | RuntimeComponent::DxvkNvApi(version) => version,
+        }
+    }
+}
+
 type ComponentVersion = String;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(hour: u32, minute: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn window_contains_its_start_but_not_its_end() {
+        let window = DownloadWindow {
+            start: time(1, 0),
+            end: time(7, 0),
+        };
+
+        assert!(window.contains(time(1, 0)));
+        assert!(window.contains(time(6, 59)));
+        assert!(!window.contains(time(7, 0)));
+        assert!(!window.contains(time(0, 59)));
+        assert!(!window.contains(time(12, 0)));
+    }
+
+    #[test]
+    fn window_wraps_around_midnight() {
+        let window = DownloadWindow {
+            start: time(22, 0),
+            end: time(6, 0),
+        };
+
+        assert!(window.contains(time(22, 0)));
+        assert!(window.contains(time(23, 59)));
+        assert!(window.contains(time(0, 0)));
+        assert!(window.contains(time(5, 59)));
+        assert!(!window.contains(time(6, 0)));
+        assert!(!window.contains(time(12, 0)));
+    }
+
+    #[test]
+    fn windows_only_hold_back_large_downloads() {
+        let now = Local::now().time();
+        // An hour that never includes now
+        let closed = DownloadWindow {
+            start: now + chrono::Duration::hours(1),
+            end: now + chrono::Duration::hours(2),
+        };
+        let settings = DownloadSettings {
+            windows: vec![closed],
+            large_download_size: 1000,
+            ..DownloadSettings::default()
+        };
+
+        assert!(settings.allows(None));
+        assert!(settings.allows(Some(999)));
+        assert!(!settings.allows(Some(1000)));
+    }
+}
@@ -0,0 +1,86 @@
+//! Copies of the last configs that loaded fine, to recover from one that
+//! doesn't.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use chrono::Local;
+
+use crate::{
+    globals::paths,
+    utils::filesystem::{ensure_dir, write_atomic},
+};
+
+const KEPT_BACKUPS: usize = 5;
+
+/// Keeps `data`, a config that just loaded fine, unless the newest backup is
+/// the same.
+pub fn backup(data: &[u8]) -> Result<(), String> {
+    let directory = &paths()?.config_backups();
+    ensure_dir(directory)?;
+
+    if latest().is_some_and(|newest| fs::read(newest).is_ok_and(|newest| newest == data)) {
+        return Ok(());
+    }
+
+    let name = format!("config-{}.json", Local::now().format("%Y%m%d-%H%M%S"));
+    write_atomic(&directory.join(name), data)?;
+
+    let backups = list();
+    for old in backups
+        .iter()
+        .take(backups.len().saturating_sub(KEPT_BACKUPS))
+    {
+        if let Err(e) = fs::remove_file(old) {
+            println!("Cannot remove old config backup {old:?}: {e}");
+        }
+    }
+
+    Ok(())
+}
+
+/// Backups, oldest first.
+pub fn list() -> Vec<PathBuf> {
+    let Ok(entries) =
+        paths().and_then(|paths| fs::read_dir(paths.config_backups()).map_err(|e| e.to_string()))
+    else {
+        return Vec::new();
+    };
+
+    let mut backups = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| is_backup(path))
+        .collect::<Vec<_>>();
+    // The names hold the time they were made
+    backups.sort();
+
+    backups
+}
+
+pub fn latest() -> Option<PathBuf> {
+    list().pop()
+}
+
+/// Moves a config that can't be loaded out of the way, so saving the
+/// defaults doesn't replace it.
+pub fn keep_corrupted() -> Result<PathBuf, String> {
+    let config_path = &paths()?.config_file();
+    let kept = config_path.with_file_name(format!(
+        "config.corrupted-{}.json",
+        Local::now().format("%Y%m%d-%H%M%S")
+    ));
+
+    fs::rename(config_path, &kept)
+        .map_err(|e| format!("Cannot move {config_path:?} to {kept:?}: {e}"))?;
+
+    Ok(kept)
+}
+
+fn is_backup(path: &Path) -> bool {
+    path.file_name()
+        .map(|name| name.to_string_lossy())
+        .is_some_and(|name| name.starts_with("config-") && name.ends_with(".json"))
+}
@@ -0,0 +1,265 @@
+//! Upgrades of `config.json` from older schemas.
+//!
+//! Migrations work on the raw JSON, before it's deserialized, so a renamed or
+//! restructured field can still be read in its old shape. `MIGRATIONS[n]`
+//! upgrades a config from schema `n` to `n + 1`, configs from before
+//! `schema_version` existed are schema 0.
+//!
+//! `fixtures/config` has a config for every schema, add one when adding a
+//! migration.
+
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
+
+use serde_json::{Map, Value};
+
+use crate::globals::data_path;
+
+type Migration = fn(&mut Map<String, Value>, &LegacyPaths) -> Result<(), String>;
+
+const MIGRATIONS: &[Migration] = &[from_unversioned, to_xdg_directories];
+
+/// Defaults of older versions, which depend on the environment. Configs still
+/// using one of them get the current default instead.
+pub struct LegacyPaths {
+    /// Held the cache and temporary files before schema 2
+    pub data_path: PathBuf,
+    /// `$XDG_RUNTIME_DIR/elysia`, the temporary files of early schema 2
+    /// configs. It's cleared on reboot, which loses paused downloads.
+    pub runtime_directory: Option<PathBuf>,
+}
+
+impl LegacyPaths {
+    pub fn current() -> Result<Self, String> {
+        Ok(Self {
+            data_path: data_path()?,
+            runtime_directory: env::var_os("XDG_RUNTIME_DIR")
+                .map(|directory| PathBuf::from(directory).join("elysia")),
+        })
+    }
+}
+
+/// Schema of the configs written by this version.
+pub const CURRENT_SCHEMA_VERSION: u32 = MIGRATIONS.len() as u32;
+
+/// Schema `config` was written with.
+pub fn schema_version(config: &Value) -> Result<u32, String> {
+    match config.get("schema_version") {
+        None => Ok(0),
+        Some(version) => version
+            .as_u64()
+            .and_then(|version| u32::try_from(version).ok())
+            .ok_or_else(|| format!("Invalid schema_version: {version}")),
+    }
+}
+
+/// Upgrades `config` step by step to the current schema.
+pub fn migrate(config: &mut Value, legacy: &LegacyPaths) -> Result<(), String> {
+    let version = schema_version(config)?;
+    if version > CURRENT_SCHEMA_VERSION {
+        return Err(format!(
+            "Config schema {version} is newer than the supported {CURRENT_SCHEMA_VERSION}, it was written by a newer version of the launcher"
+        ));
+    }
+
+    let map = config
+        .as_object_mut()
+        .ok_or_else(|| "Config is not a JSON object".to_string())?;
+    for (from, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        migration(map, legacy)
+            .map_err(|e| format!("Cannot migrate config from schema {from}: {e}"))?;
+        map.insert("schema_version".to_string(), Value::from(from + 1));
+        println!("Migrated config from schema {from} to {}", from + 1);
+    }
+
+    Ok(())
+}
+
+/// Fields added before versioning all had defaults, only the version is new.
+fn from_unversioned(_config: &mut Map<String, Value>, _legacy: &LegacyPaths) -> Result<(), String> {
+    Ok(())
+}
+
+/// The cache and temporary files left the data directory, configs still using
+/// the old defaults get the new ones. The cache itself is moved by
+/// `globals::migrate_legacy_layout`, queued downloads keep their directory.
+fn to_xdg_directories(config: &mut Map<String, Value>, legacy: &LegacyPaths) -> Result<(), String> {
+    let data_path = &legacy.data_path;
+    remove_default(config, "cache_directory", &data_path.join("cache"));
+    remove_default(config, "temp_directory", &data_path.join("temp"));
+    if let Some(runtime_directory) = &legacy.runtime_directory {
+        remove_default(config, "temp_directory", runtime_directory);
+    }
+
+    Ok(())
+}
+
+/// Removes the path at `key` when it's `default`, so the current default is
+/// used instead.
+fn remove_default(config: &mut Map<String, Value>, key: &str, default: &Path) {
+    let is_default = config
+        .get(key)
+        .and_then(Value::as_str)
+        .is_some_and(|path| Path::new(path) == default);
+    if is_default {
+        config.remove(key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, fs};
+
+    use super::*;
+    use crate::{
+        runners::{Proton, Runner, Wine},
+        settings::InstalledGame,
+    };
+
+    const GAME_ID: &str = "U5hbdsT9W7";
+    const OLD_DATA_PATH: &str = "/home/elysia/.local/share/elysia";
+
+    // Passed in, so nothing depends on the environment of the test run
+    fn legacy() -> LegacyPaths {
+        LegacyPaths {
+            data_path: PathBuf::from(OLD_DATA_PATH),
+            runtime_directory: Some(PathBuf::from("/run/user/1000/elysia")),
+        }
+    }
+
+    /// A fixture migrated to the current schema, still as JSON so the
+    /// directories that fall back to their defaults show up as missing.
+    fn migrate_fixture(name: &str) -> Map<String, Value> {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("fixtures/config")
+            .join(format!("{name}.json"));
+        let mut config = serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap();
+
+        migrate(&mut config, &legacy()).unwrap();
+
+        match config {
+            Value::Object(config) => config,
+            _ => panic!("{name} is not an object"),
+        }
+    }
+
+    fn path(config: &Map<String, Value>, key: &str) -> Option<PathBuf> {
+        config.get(key).map(|path| {
+            serde_json::from_value(path.clone()).unwrap_or_else(|e| panic!("{key}: {e}"))
+        })
+    }
+
+    /// Runner version of the installed game, which every fixture has.
+    fn runner_version(config: &Map<String, Value>) -> String {
+        let games: HashMap<String, InstalledGame> =
+            serde_json::from_value(config["installed_games"].clone()).unwrap();
+
+        match &games[GAME_ID].runner {
+            Runner::Wine(Wine { version, .. }) | Runner::Proton(Proton { version, .. }) => {
+                version.clone()
+            }
+            Runner::Native => "native".to_string(),
+        }
+    }
+
+    fn assert_kept(config: &Map<String, Value>) {
+        let old_data_path = Path::new(OLD_DATA_PATH);
+
+        assert_eq!(
+            schema_version(&Value::Object(config.clone())),
+            Ok(CURRENT_SCHEMA_VERSION)
+        );
+        assert_eq!(
+            path(config, "wineprefixes_directory"),
+            Some(old_data_path.join("wineprefixes"))
+        );
+        assert_eq!(
+            path(config, "components_directory"),
+            Some(old_data_path.join("components"))
+        );
+        assert_eq!(
+            config["installed_games"][GAME_ID]["install_path"],
+            "/home/elysia/Games/ZenlessZoneZero"
+        );
+    }
+
+    #[test]
+    fn migrates_initial_config() {
+        let config = migrate_fixture("schema-0-initial");
+
+        assert_kept(&config);
+        assert_eq!(runner_version(&config), "Spritz-Wine-TkG-10.15-3");
+        assert_eq!(path(&config, "games_directory"), None);
+        assert_eq!(path(&config, "cache_directory"), None);
+        assert_eq!(path(&config, "temp_directory"), None);
+    }
+
+    #[test]
+    fn migrates_configs_from_the_data_directory() {
+        for name in ["schema-0-unversioned", "schema-1"] {
+            let config = migrate_fixture(name);
+
+            assert_kept(&config);
+            assert_eq!(runner_version(&config), "GE-Proton10-15");
+            assert_eq!(
+                path(&config, "games_directory"),
+                Some(Path::new(OLD_DATA_PATH).join("games"))
+            );
+            assert_eq!(path(&config, "cache_directory"), None, "{name}");
+            assert_eq!(path(&config, "temp_directory"), None, "{name}");
+        }
+    }
+
+    #[test]
+    fn moves_temp_directory_out_of_runtime_directory() {
+        let mut config = serde_json::json!({
+            "schema_version": 1,
+            "temp_directory": "/run/user/1000/elysia",
+        });
+
+        migrate(&mut config, &legacy()).unwrap();
+        assert_eq!(config, serde_json::json!({ "schema_version": 2 }));
+    }
+
+    #[test]
+    fn keeps_directories_chosen_by_the_user() {
+        let mut config = serde_json::json!({
+            "schema_version": 1,
+            "temp_directory": "/mnt/fast/elysia-temp",
+            "cache_directory": "/home/elysia/.local/share/elysia/other-cache",
+        });
+        let expected = serde_json::json!({
+            "schema_version": 2,
+            "temp_directory": "/mnt/fast/elysia-temp",
+            "cache_directory": "/home/elysia/.local/share/elysia/other-cache",
+        });
+
+        migrate(&mut config, &legacy()).unwrap();
+        assert_eq!(config, expected);
+    }
+
+    #[test]
+    fn keeps_current_config() {
+        let config = migrate_fixture("schema-2");
+
+        assert_kept(&config);
+        assert_eq!(runner_version(&config), "GE-Proton10-15");
+        assert_eq!(
+            path(&config, "cache_directory"),
+            Some(PathBuf::from("/home/elysia/.cache/elysia"))
+        );
+        assert_eq!(
+            path(&config, "temp_directory"),
+            Some(PathBuf::from("/home/elysia/.cache/elysia/downloads"))
+        );
+    }
+
+    #[test]
+    fn rejects_newer_schema() {
+        let mut config = serde_json::json!({ "schema_version": CURRENT_SCHEMA_VERSION + 1 });
+
+        assert!(migrate(&mut config, &legacy()).is_err());
+    }
+}
@@ -0,0 +1,82 @@
+use std::{
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use reqwest::header::{ETAG, HeaderMap, LAST_MODIFIED};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CachePolicy {
+    /// Return cached data right away, revalidating it in the background once stale
+    PreferCache,
+    /// Skip the cache and always hit the network
+    Bypass,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheEntry<Type> {
+    /// Seconds since the unix epoch
+    pub fetched_at: u64,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub data: Type,
+}
+
+impl<Type> CacheEntry<Type> {
+    pub fn new(data: Type, headers: &HeaderMap) -> Self {
+        let header = |name| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        };
+
+        Self {
+            fetched_at: now(),
+            etag: header(ETAG),
+            last_modified: header(LAST_MODIFIED),
+            data,
+        }
+    }
+
+    pub fn is_fresh(&self, ttl: Duration) -> bool {
+        now().saturating_sub(self.fetched_at) < ttl.as_secs()
+    }
+
+    /// Marks the entry as fresh again after the server confirmed it's unchanged.
+    pub fn touch(&mut self) {
+        self.fetched_at = now();
+    }
+}
+
+pub fn read_entry<Type>(cache_path: &Path, key: &str) -> Option<CacheEntry<Type>>
+where
+    Type: DeserializeOwned,
+{
+    let asset = cacache::read_sync(cache_path, key).ok()?;
+
+    // Entries written before validators were stored don't parse and count as a miss
+    serde_json::from_slice(&asset).ok()
+}
+
+pub fn write_entry<Type>(cache_path: &Path, key: &str, entry: &CacheEntry<Type>)
+where
+    Type: Serialize,
+{
+    match serde_json::to_vec(entry) {
+        Ok(data) => {
+            if let Err(e) = cacache::write_sync(cache_path, key, data) {
+                println!("Error when writing cache entry for {key}: {e}");
+            }
+        }
+        Err(e) => println!("Error when serializing cache entry for {key}: {e}"),
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
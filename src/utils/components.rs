@@ -0,0 +1,420 @@
+//! Verified installs of downloaded components: umu-launcher, Wine and Proton
+//! builds, DXVK and VKD3D-Proton.
+//!
+//! Every archive is checked against its published checksum before it's
+//! unpacked, and what was verified is recorded in `manifest.json` in the
+//! components directory. A later download of the same version that hashes
+//! differently is rejected, and installed files are checked against the
+//! manifest before they're used.
+
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::utils::{
+    download::download_file,
+    extract::extract,
+    filesystem::{ensure_dir, write_atomic},
+    integrity::{Checksum, fetch_checksum, sha256_file, verify_file},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ComponentKind {
+    Umu,
+    Proton,
+    Wine,
+    Dxvk,
+    Vkd3dProton,
+}
+
+impl fmt::Display for ComponentKind {
+    /// Also the components subdirectory of the builds, except for umu
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ComponentKind::Umu => write!(f, "umu"),
+            ComponentKind::Proton => write!(f, "proton"),
+            ComponentKind::Wine => write!(f, "wine"),
+            ComponentKind::Dxvk => write!(f, "dxvk"),
+            ComponentKind::Vkd3dProton => write!(f, "vkd3d-proton"),
+        }
+    }
+}
+
+/// Where the checksum of a release comes from.
+#[derive(Debug, Clone)]
+pub enum ChecksumSource {
+    /// Pinned in the launcher
+    Known(Checksum),
+    /// A `sha256sum`/`sha512sum` file published next to the release
+    File(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct ComponentRelease {
+    pub kind: ComponentKind,
+    pub version: String,
+    pub url: String,
+    pub checksum: ChecksumSource,
+    /// Directory the archive is unpacked into, relative to the components directory
+    pub extract_to: PathBuf,
+    /// Whether the single directory the archive holds becomes `extract_to`
+    /// itself, instead of being unpacked inside of it
+    pub unwrap_directory: bool,
+    /// Files that must exist after unpacking, relative to the components
+    /// directory. Their hashes are recorded to detect tampering later.
+    pub files: Vec<PathBuf>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ComponentRecord {
+    pub kind: ComponentKind,
+    pub version: String,
+    pub archive: Checksum,
+    /// sha256 of `ComponentRelease::files`
+    pub files: HashMap<PathBuf, String>,
+    /// Seconds since the unix epoch
+    pub verified_at: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ComponentManifest {
+    /// Keyed by `<kind>/<version>`
+    pub components: HashMap<String, ComponentRecord>,
+}
+
+impl ComponentManifest {
+    pub fn load(components_directory: &Path) -> ComponentManifest {
+        let path = Self::path(components_directory);
+        let Ok(data) = fs::read_to_string(&path) else {
+            return ComponentManifest::default();
+        };
+
+        serde_json::from_str(&data).unwrap_or_else(|e| {
+            println!("Error when loading component manifest {path:?}, starting empty: {e}");
+            ComponentManifest::default()
+        })
+    }
+
+    pub fn save(&self, components_directory: &Path) -> Result<(), String> {
+        let path = Self::path(components_directory);
+        let data = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Cannot serialize component manifest: {e}"))?;
+
+        ensure_dir(components_directory)?;
+        write_atomic(&path, data.as_bytes())
+    }
+
+    pub fn get(&self, kind: ComponentKind, version: &str) -> Option<&ComponentRecord> {
+        self.components.get(&key(kind, version))
+    }
+
+    pub fn get_mut(&mut self, kind: ComponentKind, version: &str) -> Option<&mut ComponentRecord> {
+        self.components.get_mut(&key(kind, version))
+    }
+
+    pub fn remove(&mut self, kind: ComponentKind, version: &str) -> Option<ComponentRecord> {
+        self.components.remove(&key(kind, version))
+    }
+
+    fn path(components_directory: &Path) -> PathBuf {
+        components_directory.join("manifest.json")
+    }
+}
+
+fn key(kind: ComponentKind, version: &str) -> String {
+    format!("{kind}/{version}")
+}
+
+/// Checks an installed component against the manifest, fails when it was
+/// never verified or one of its files changed.
+pub fn verify_installed(
+    components_directory: &Path,
+    kind: ComponentKind,
+    version: &str,
+) -> Result<(), String> {
+    let manifest = ComponentManifest::load(components_directory);
+    let record = manifest
+        .get(kind, version)
+        .ok_or_else(|| format!("{kind} {version} is not in the component manifest"))?;
+
+    for (file, expected) in &record.files {
+        let path = components_directory.join(file);
+        let actual = sha256_file(&path)?;
+        if actual != *expected {
+            return Err(format!("{path:?} was modified since it was installed"));
+        }
+    }
+
+    Ok(())
+}
+
+/// Like `verify_installed`, but lets through builds that weren't installed by
+/// the launcher, e.g. ones unpacked there by hand.
+pub fn verify_if_recorded(
+    components_directory: &Path,
+    kind: ComponentKind,
+    version: &str,
+) -> Result<(), String> {
+    let recorded = ComponentManifest::load(components_directory)
+        .get(kind, version)
+        .is_some();
+    if !recorded {
+        return Ok(());
+    }
+
+    verify_installed(components_directory, kind, version)
+}
+
+/// Downloads, verifies and unpacks a component, then records it in the manifest.
+pub async fn install_component(
+    release: &ComponentRelease,
+    components_directory: &Path,
+    temp_directory: &Path,
+) -> Result<(), String> {
+    let ComponentRelease { kind, version, .. } = release;
+    ensure_dir(temp_directory)?;
+
+    let file_name = release
+        .url
+        .rsplit('/')
+        .next()
+        .filter(|name| !name.is_empty())
+        .ok_or_else(|| format!("Invalid component url: {}", release.url))?;
+    let archive_path = temp_directory.join(file_name);
+
+    println!("Downloading {kind} {version}..");
+    download_file(&release.url, &archive_path).await?;
+
+    let expected = match &release.checksum {
+        ChecksumSource::Known(checksum) => checksum.clone(),
+        ChecksumSource::File(url) => fetch_checksum(url, file_name).await?,
+    };
+    let previous = ComponentManifest::load(components_directory)
+        .get(*kind, version)
+        .map(|record| record.archive.clone());
+    if let Err(e) = check_archive(&archive_path, &expected, previous.as_ref()) {
+        let _ = fs::remove_file(&archive_path);
+        return Err(format!("Refusing to install {kind} {version}: {e}"));
+    }
+
+    let destination = components_directory.join(&release.extract_to);
+    let extracted = if release.unwrap_directory {
+        // Unpacked next to the builds, so moving it in place is a rename
+        let staging = components_directory
+            .join(".unpacking")
+            .join(format!("{kind}-{version}"));
+        let _ = fs::remove_dir_all(&staging);
+        let extracted = match extract(std::slice::from_ref(&archive_path), &staging).await {
+            Ok(()) => move_unpacked(&staging, &destination),
+            Err(e) => Err(e),
+        };
+        let _ = fs::remove_dir_all(&staging);
+        extracted
+    } else {
+        extract(std::slice::from_ref(&archive_path), &destination).await
+    };
+    let _ = fs::remove_file(&archive_path);
+    extracted?;
+
+    let mut files = HashMap::new();
+    for file in &release.files {
+        let path = components_directory.join(file);
+        if !path.exists() {
+            return Err(format!(
+                "{path:?} not found after installing {kind} {version}"
+            ));
+        }
+        files.insert(file.clone(), sha256_file(&path)?);
+    }
+
+    let verified_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+    let mut manifest = ComponentManifest::load(components_directory);
+    manifest.components.insert(
+        key(*kind, version),
+        ComponentRecord {
+            kind: *kind,
+            version: version.clone(),
+            archive: expected,
+            files,
+            verified_at,
+        },
+    );
+    manifest.save(components_directory)?;
+
+    println!("{kind} {version} installed and verified.");
+
+    Ok(())
+}
+
+/// Fails unless the archive hashes to `expected`, and to what was verified
+/// for the same version before.
+fn check_archive(
+    archive_path: &Path,
+    expected: &Checksum,
+    previous: Option<&Checksum>,
+) -> Result<(), String> {
+    verify_file(archive_path, expected)?;
+
+    match previous {
+        // Same version, different archive: the release was replaced upstream
+        Some(previous) if previous != expected => Err(format!(
+            "the archive doesn't match the one verified before ({previous:?})"
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Moves what was unpacked in `staging` to `destination`, the directory inside
+/// of it when there's only one. Replaces a previous, unverified, install.
+fn move_unpacked(staging: &Path, destination: &Path) -> Result<(), String> {
+    let entries = fs::read_dir(staging)
+        .map_err(|e| format!("Cannot read {staging:?}: {e}"))?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .collect::<Vec<_>>();
+    let source = match entries.as_slice() {
+        [single] if single.is_dir() => single,
+        _ => staging,
+    };
+
+    if destination.exists() {
+        fs::remove_dir_all(destination)
+            .map_err(|e| format!("Cannot replace {destination:?}: {e}"))?;
+    }
+    if let Some(parent) = destination.parent() {
+        ensure_dir(parent)?;
+    }
+
+    fs::rename(source, destination)
+        .map_err(|e| format!("Cannot move {source:?} to {destination:?}: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("elysia-components-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        dir
+    }
+
+    #[test]
+    fn manifest_survives_a_round_trip() {
+        let dir = temp_dir("manifest");
+        let mut manifest = ComponentManifest::default();
+        manifest.components.insert(
+            key(ComponentKind::Proton, "GE-Proton10-15"),
+            ComponentRecord {
+                kind: ComponentKind::Proton,
+                version: "GE-Proton10-15".to_string(),
+                archive: Checksum::Sha512("ab".repeat(64)),
+                files: HashMap::from([(
+                    PathBuf::from("proton/GE-Proton10-15/proton"),
+                    "cd".repeat(32),
+                )]),
+                verified_at: 1_700_000_000,
+            },
+        );
+
+        manifest.save(&dir).unwrap();
+        let loaded = ComponentManifest::load(&dir);
+        let leftovers = fs::read_dir(&dir).unwrap().count();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let record = loaded.get(ComponentKind::Proton, "GE-Proton10-15").unwrap();
+        assert_eq!(record.archive, Checksum::Sha512("ab".repeat(64)));
+        assert_eq!(
+            record.files[Path::new("proton/GE-Proton10-15/proton")],
+            "cd".repeat(32)
+        );
+        assert_eq!(record.verified_at, 1_700_000_000);
+        assert!(loaded.get(ComponentKind::Wine, "GE-Proton10-15").is_none());
+        // Written through a temporary file that's gone afterwards
+        assert_eq!(leftovers, 1);
+    }
+
+    #[test]
+    fn rejects_tampered_archives() {
+        let dir = temp_dir("tampered");
+        let archive = dir.join("dxvk-2.4.tar.gz");
+        fs::write(&archive, "the published archive").unwrap();
+        let published = Checksum::Sha256(sha256_file(&archive).unwrap());
+
+        assert!(check_archive(&archive, &published, None).is_ok());
+        assert!(check_archive(&archive, &published, Some(&published)).is_ok());
+
+        fs::write(&archive, "the published archive, with a payload").unwrap();
+        let tampered = check_archive(&archive, &published, None);
+        // Republished with a new checksum, but differing from the first install
+        let replaced = Checksum::Sha256(sha256_file(&archive).unwrap());
+        let republished = check_archive(&archive, &replaced, Some(&published));
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(tampered.unwrap_err().contains("Checksum mismatch"));
+        assert!(republished.unwrap_err().contains("verified before"));
+    }
+
+    #[test]
+    fn tampered_files_fail_verification() {
+        let dir = temp_dir("installed");
+        let file = PathBuf::from("wine/GE-Proton8-26/bin/wine");
+        fs::create_dir_all(dir.join(file.parent().unwrap())).unwrap();
+        fs::write(dir.join(&file), "wine").unwrap();
+        let mut manifest = ComponentManifest::default();
+        manifest.components.insert(
+            key(ComponentKind::Wine, "GE-Proton8-26"),
+            ComponentRecord {
+                kind: ComponentKind::Wine,
+                version: "GE-Proton8-26".to_string(),
+                archive: Checksum::Sha256("00".repeat(32)),
+                files: HashMap::from([(file.clone(), sha256_file(&dir.join(&file)).unwrap())]),
+                verified_at: 0,
+            },
+        );
+        manifest.save(&dir).unwrap();
+
+        let untouched = verify_installed(&dir, ComponentKind::Wine, "GE-Proton8-26");
+        fs::write(dir.join(&file), "not wine").unwrap();
+        let modified = verify_installed(&dir, ComponentKind::Wine, "GE-Proton8-26");
+        let unknown = verify_installed(&dir, ComponentKind::Wine, "GE-Proton9-1");
+        let unknown_if_recorded = verify_if_recorded(&dir, ComponentKind::Wine, "GE-Proton9-1");
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(untouched.is_ok());
+        assert!(modified.is_err());
+        assert!(unknown.is_err());
+        assert!(unknown_if_recorded.is_ok());
+    }
+
+    #[test]
+    fn unwraps_the_single_directory_of_an_archive() {
+        let dir = temp_dir("unwrap");
+        let staging = dir.join("staging");
+        fs::create_dir_all(staging.join("dxvk-2.4/x64")).unwrap();
+        fs::write(staging.join("dxvk-2.4/x64/d3d11.dll"), "dll").unwrap();
+        let destination = dir.join("dxvk/2.4");
+        // Left by an install that failed verification
+        fs::create_dir_all(destination.join("old")).unwrap();
+
+        move_unpacked(&staging, &destination).unwrap();
+        let unwrapped = destination.join("x64/d3d11.dll").exists();
+        let replaced = !destination.join("old").exists();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(unwrapped);
+        assert!(replaced);
+    }
+}
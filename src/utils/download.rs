@@ -0,0 +1,1180 @@
+//! Shared download manager used for game packages, runners, components and umu.
+//!
+//! Downloads go through `.part` files and resume with HTTP Range requests, big
+//! files are split over several connections, and every download publishes its
+//! progress in a signal the UI can read. Unfinished downloads are saved to
+//! disk and continue after a restart.
+//!
+//! `DownloadSettings` are read as downloads run, so the bandwidth limit, the
+//! download windows and pausing while playing apply without a restart.
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    fs,
+    io::Read,
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use dioxus::prelude::{Task, consume_context, spawn_forever};
+use freya::prelude::{Readable, ScopeId, Signal, Writable};
+use futures::channel::oneshot;
+use md5::{Digest, Md5};
+use reqwest::{
+    StatusCode,
+    header::{ACCEPT_RANGES, CONTENT_LENGTH, CONTENT_RANGE, RANGE},
+};
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+
+use crate::{
+    globals::{from_stored_path, paths, to_stored_path},
+    settings::GlobalSettings,
+    utils::{
+        filesystem::{ensure_dir, write_atomic},
+        process,
+    },
+};
+
+const MAX_CONCURRENT_DOWNLOADS: usize = 3;
+const CONNECTIONS_PER_FILE: u64 = 4;
+/// Files are only split when every connection gets at least this much
+const MIN_SEGMENT_SIZE: u64 = 16 * 1024 * 1024;
+const MAX_RETRIES: usize = 3;
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+/// Completed downloads kept around for the downloads page
+const MAX_COMPLETED: usize = 50;
+/// How often download windows and running games are checked
+const SCHEDULE_CHECK_INTERVAL: Duration = Duration::from_secs(15);
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DownloadState {
+    /// Waiting for a free download slot
+    Queued,
+    Downloading,
+    /// Held back by the download settings, starts on its own later
+    Waiting(WaitReason),
+    Paused,
+    Failed(String),
+    Completed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WaitReason {
+    /// Large download outside of the download windows
+    Window,
+    GameRunning,
+}
+
+impl DownloadState {
+    pub fn is_completed(&self) -> bool {
+        matches!(self, DownloadState::Completed)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DownloadProgress {
+    pub state: DownloadState,
+    pub downloaded: u64,
+    pub total: Option<u64>,
+    /// Bytes per second
+    pub speed: u64,
+    /// Archive bytes read and the archive size while an install unpacks it
+    pub unpacking: Option<(u64, u64)>,
+}
+
+impl DownloadProgress {
+    /// Time left at the current speed, when both are known.
+    pub fn eta(&self) -> Option<Duration> {
+        let total = self.total?;
+        if self.speed == 0 {
+            return None;
+        }
+
+        Some(Duration::from_secs(
+            total.saturating_sub(self.downloaded) / self.speed,
+        ))
+    }
+}
+
+#[derive(Clone, PartialEq)]
+pub struct DownloadTask {
+    pub id: u64,
+    pub name: String,
+    pub url: String,
+    pub target: PathBuf,
+    pub progress: Signal<DownloadProgress>,
+}
+
+/// An unfinished download as written to `downloads.json`.
+#[derive(Serialize, Deserialize)]
+struct SavedTask {
+    name: String,
+    url: String,
+    target: PathBuf,
+    state: DownloadState,
+    total: Option<u64>,
+}
+
+/// An install waiting on its downloads. Saved with the queue so it carries on
+/// with the restored downloads after a restart.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InstallIntent {
+    pub game_id: String,
+    /// Id of the server config the packages come from
+    pub server: Option<String>,
+}
+
+/// Everything in `downloads.json`, which used to hold only the tasks.
+#[derive(Default, Serialize, Deserialize)]
+struct SavedQueue {
+    downloads: Vec<SavedTask>,
+    #[serde(default)]
+    installs: Vec<InstallIntent>,
+}
+
+type Waiter = oneshot::Sender<Result<(), String>>;
+
+#[derive(Clone)]
+pub struct DownloadManager {
+    client: reqwest::Client,
+    settings: Signal<GlobalSettings>,
+    throttle: Rc<RefCell<Throttle>>,
+    /// Whether an installed game was running at the last check
+    playing: Rc<Cell<bool>>,
+    next_id: Rc<Cell<u64>>,
+    /// Running downloads, cancelled to pause or cancel them
+    running: Rc<RefCell<HashMap<u64, Task>>>,
+    /// Callers waiting for a download to end
+    waiters: Rc<RefCell<HashMap<u64, Vec<Waiter>>>>,
+    /// Installs that still need their downloads
+    installs: Rc<RefCell<Vec<InstallIntent>>>,
+    /// Every download in priority order
+    pub tasks: Signal<Vec<DownloadTask>>,
+}
+
+impl DownloadManager {
+    pub fn new(settings: Signal<GlobalSettings>) -> Self {
+        let manager = Self {
+            client: reqwest::Client::new(),
+            settings,
+            throttle: Rc::default(),
+            playing: Rc::default(),
+            next_id: Rc::new(Cell::new(0)),
+            running: Rc::default(),
+            waiters: Rc::default(),
+            installs: Rc::default(),
+            tasks: Signal::new_in_scope(Vec::new(), ScopeId::ROOT),
+        };
+
+        let queue = load_queue();
+        *manager.installs.borrow_mut() = queue.installs;
+        for saved in queue.downloads {
+            let state = match saved.state {
+                DownloadState::Downloading | DownloadState::Waiting(_) => DownloadState::Queued,
+                state => state,
+            };
+            let target = from_stored_path(&saved.target);
+            manager.add_task(saved.name, saved.url, target, state, saved.total);
+        }
+
+        // Unfinished downloads from the last session continue right away
+        let ticker = manager.clone();
+        spawn_forever(async move {
+            loop {
+                ticker.check_schedule();
+                tokio::time::sleep(SCHEDULE_CHECK_INTERVAL).await;
+            }
+        });
+
+        manager
+    }
+
+    /// Queues a download of `url` to `target` and waits for it to complete.
+    /// A download of the same file left over from a previous session is
+    /// picked up instead of starting over.
+    pub async fn download(&self, url: &str, target: &Path) -> Result<(), String> {
+        if let Some(parent) = target.parent() {
+            ensure_dir(parent)?;
+        }
+
+        let existing = self
+            .tasks
+            .peek()
+            .iter()
+            .find(|task| task.target == target && !task.progress.peek().state.is_completed())
+            .map(|task| task.id);
+        let id = match existing {
+            Some(id) => {
+                self.resume(id);
+                id
+            }
+            None => {
+                let name = target
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_else(|| url.to_string());
+                let id = self.add_task(
+                    name,
+                    url.to_string(),
+                    target.to_path_buf(),
+                    DownloadState::Queued,
+                    None,
+                );
+                self.schedule();
+                self.save();
+                id
+            }
+        };
+
+        let (sender, receiver) = oneshot::channel();
+        self.waiters
+            .borrow_mut()
+            .entry(id)
+            .or_default()
+            .push(sender);
+
+        receiver
+            .await
+            .unwrap_or_else(|_| Err(format!("Download of {target:?} was cancelled")))
+    }
+
+    pub fn pause(&self, id: u64) {
+        let Some(task) = self.task(id) else {
+            return;
+        };
+        if !matches!(
+            task.progress.peek().state,
+            DownloadState::Queued | DownloadState::Downloading | DownloadState::Waiting(_)
+        ) {
+            return;
+        }
+
+        self.hold(&task, DownloadState::Paused);
+        self.schedule();
+        self.save();
+    }
+
+    /// Queues a paused or failed download again.
+    pub fn resume(&self, id: u64) {
+        let Some(task) = self.task(id) else {
+            return;
+        };
+        if !matches!(
+            task.progress.peek().state,
+            DownloadState::Paused | DownloadState::Failed(_)
+        ) {
+            return;
+        }
+
+        let mut progress = task.progress;
+        progress.write().state = DownloadState::Queued;
+
+        self.schedule();
+        self.save();
+    }
+
+    /// Stops a download and deletes what was downloaded of it so far.
+    pub fn cancel(&self, id: u64) {
+        let Some(task) = self.task(id) else {
+            return;
+        };
+
+        if let Some(running) = self.running.borrow_mut().remove(&id) {
+            running.cancel();
+        }
+        if !task.progress.peek().state.is_completed() {
+            remove_parts(&task.target);
+        }
+        // Dropping the senders tells the waiting callers it was cancelled
+        self.waiters.borrow_mut().remove(&id);
+        self.remove_task(id);
+
+        self.schedule();
+        self.save();
+    }
+
+    /// Moves a download up (negative `offset`) or down the queue.
+    pub fn reorder(&self, id: u64, offset: isize) {
+        let mut tasks = self.tasks;
+        {
+            let mut tasks = tasks.write();
+            let Some(index) = tasks.iter().position(|task| task.id == id) else {
+                return;
+            };
+            let new_index = index
+                .saturating_add_signed(offset)
+                .min(tasks.len().saturating_sub(1));
+            let task = tasks.remove(index);
+            tasks.insert(new_index, task);
+        }
+
+        self.schedule();
+        self.save();
+    }
+
+    /// Cancels the unfinished downloads into `directory`.
+    pub fn cancel_in(&self, directory: &Path) {
+        let ids = self
+            .tasks
+            .peek()
+            .iter()
+            .filter(|task| {
+                task.target.starts_with(directory) && !task.progress.peek().state.is_completed()
+            })
+            .map(|task| task.id)
+            .collect::<Vec<_>>();
+        for id in ids {
+            self.cancel(id);
+        }
+    }
+
+    /// Shows the unpacking progress of an install on its downloads into
+    /// `directory`.
+    pub fn set_unpacking_in(&self, directory: &Path, unpacking: Option<(u64, u64)>) {
+        for task in self.tasks.peek().iter() {
+            let mut progress = task.progress;
+            if task.target.starts_with(directory) && progress.peek().unpacking != unpacking {
+                progress.write().unpacking = unpacking;
+            }
+        }
+    }
+
+    /// Whether any download is still to finish, paused ones included as their
+    /// `.part` files wait where they are.
+    pub fn has_unfinished(&self) -> bool {
+        self.tasks.peek().iter().any(|task| {
+            !matches!(
+                task.progress.peek().state,
+                DownloadState::Completed | DownloadState::Failed(_)
+            )
+        })
+    }
+
+    /// Removes completed downloads from the list.
+    pub fn clear_completed(&self) {
+        let completed = self
+            .tasks
+            .peek()
+            .iter()
+            .filter(|task| task.progress.peek().state.is_completed())
+            .map(|task| task.id)
+            .collect::<Vec<_>>();
+        for id in completed {
+            self.remove_task(id);
+        }
+    }
+
+    /// Remembers an install until `end_install`, replacing an earlier one of
+    /// the same game.
+    pub fn begin_install(&self, intent: InstallIntent) {
+        {
+            let mut installs = self.installs.borrow_mut();
+            installs.retain(|install| install.game_id != intent.game_id);
+            installs.push(intent);
+        }
+        self.save();
+    }
+
+    pub fn end_install(&self, game_id: &str) {
+        self.installs
+            .borrow_mut()
+            .retain(|install| install.game_id != game_id);
+        self.save();
+    }
+
+    /// Installs that were interrupted, oldest first.
+    pub fn pending_installs(&self) -> Vec<InstallIntent> {
+        self.installs.borrow().clone()
+    }
+
+    /// Stops a download where it is, keeping its `.part` files.
+    fn hold(&self, task: &DownloadTask, state: DownloadState) {
+        if let Some(running) = self.running.borrow_mut().remove(&task.id) {
+            running.cancel();
+        }
+
+        let mut progress = task.progress;
+        let mut progress = progress.write();
+        progress.state = state;
+        progress.speed = 0;
+    }
+
+    /// Why a download of `size` bytes can't run right now, if it can't.
+    fn wait_reason(&self, size: Option<u64>) -> Option<WaitReason> {
+        let settings = self.settings.peek();
+        if settings.downloads.pause_while_playing && self.playing.get() {
+            Some(WaitReason::GameRunning)
+        } else if !settings.downloads.allows(size) {
+            Some(WaitReason::Window)
+        } else {
+            None
+        }
+    }
+
+    /// Holds back downloads the settings don't allow right now and releases
+    /// the ones they allow again.
+    fn check_schedule(&self) {
+        let playing = {
+            let settings = self.settings.peek();
+            settings.downloads.pause_while_playing
+                && settings.installed_games.values().any(|game| {
+                    game.executable_path
+                        .file_name()
+                        .is_some_and(|name| process::is_running(&name.to_string_lossy()))
+                })
+        };
+        self.playing.set(playing);
+
+        let tasks = self.tasks.peek().clone();
+        for task in tasks {
+            let (state, total) = {
+                let progress = task.progress.peek();
+                (progress.state.clone(), progress.total)
+            };
+            let reason = self.wait_reason(total);
+
+            match (state, reason) {
+                (DownloadState::Queued | DownloadState::Downloading, Some(reason)) => {
+                    self.hold(&task, DownloadState::Waiting(reason));
+                }
+                (DownloadState::Waiting(previous), reason) if Some(previous) != reason => {
+                    let mut progress = task.progress;
+                    progress.write().state = reason
+                        .map(DownloadState::Waiting)
+                        .unwrap_or(DownloadState::Queued);
+                }
+                _ => {}
+            }
+        }
+
+        self.schedule();
+        self.save();
+    }
+
+    fn task(&self, id: u64) -> Option<DownloadTask> {
+        self.tasks.peek().iter().find(|task| task.id == id).cloned()
+    }
+
+    fn add_task(
+        &self,
+        name: String,
+        url: String,
+        target: PathBuf,
+        state: DownloadState,
+        total: Option<u64>,
+    ) -> u64 {
+        let id = self.next_id.get();
+        self.next_id.set(id + 1);
+
+        let task = DownloadTask {
+            id,
+            name,
+            url,
+            target,
+            progress: Signal::new_in_scope(
+                DownloadProgress {
+                    state,
+                    downloaded: 0,
+                    total,
+                    speed: 0,
+                    unpacking: None,
+                },
+                ScopeId::ROOT,
+            ),
+        };
+        let mut tasks = self.tasks;
+        tasks.write().push(task);
+
+        id
+    }
+
+    fn remove_task(&self, id: u64) {
+        let mut tasks = self.tasks;
+        let mut tasks = tasks.write();
+        if let Some(index) = tasks.iter().position(|task| task.id == id) {
+            tasks.remove(index).progress.manually_drop();
+        }
+    }
+
+    /// Starts queued downloads, in order, until all slots are taken.
+    fn schedule(&self) {
+        let (running, queued) = {
+            let tasks = self.tasks.peek();
+            let running = tasks
+                .iter()
+                .filter(|task| task.progress.peek().state == DownloadState::Downloading)
+                .count();
+            let queued = tasks
+                .iter()
+                .filter(|task| task.progress.peek().state == DownloadState::Queued)
+                .cloned()
+                .collect::<Vec<_>>();
+            (running, queued)
+        };
+
+        let mut free = MAX_CONCURRENT_DOWNLOADS.saturating_sub(running);
+        for task in queued {
+            if free == 0 {
+                break;
+            }
+            if self.start(task) {
+                free -= 1;
+            }
+        }
+    }
+
+    /// Starts a download unless the settings hold it back, returns whether it
+    /// was started.
+    fn start(&self, task: DownloadTask) -> bool {
+        let mut progress = task.progress;
+        if let Some(reason) = self.wait_reason(progress.peek().total) {
+            progress.write().state = DownloadState::Waiting(reason);
+            return false;
+        }
+        progress.write().state = DownloadState::Downloading;
+
+        let id = task.id;
+        let manager = self.clone();
+        let handle = spawn_forever(async move {
+            let (total, ranges) = manager.probe(&task.url).await;
+            if total.is_some() {
+                progress.write().total = total;
+            }
+
+            // The size of new downloads is only known now
+            if let Some(reason) = manager.wait_reason(total) {
+                manager.running.borrow_mut().remove(&task.id);
+                progress.write().state = DownloadState::Waiting(reason);
+                manager.schedule();
+                manager.save();
+                return;
+            }
+
+            let result = manager.run(&task, total, ranges).await;
+            manager.finish(task.id, result);
+        });
+        if let Some(handle) = handle {
+            self.running.borrow_mut().insert(id, handle);
+        }
+
+        true
+    }
+
+    fn finish(&self, id: u64, result: Result<(), String>) {
+        self.running.borrow_mut().remove(&id);
+
+        if let Some(task) = self.task(id) {
+            let mut progress = task.progress;
+            let mut progress = progress.write();
+            progress.speed = 0;
+            progress.state = match &result {
+                Ok(()) => DownloadState::Completed,
+                Err(e) => DownloadState::Failed(e.clone()),
+            };
+        }
+
+        for waiter in self.waiters.borrow_mut().remove(&id).unwrap_or_default() {
+            let _ = waiter.send(result.clone());
+        }
+
+        self.prune_completed();
+        self.schedule();
+        self.save();
+    }
+
+    fn prune_completed(&self) {
+        let completed = self
+            .tasks
+            .peek()
+            .iter()
+            .filter(|task| task.progress.peek().state.is_completed())
+            .map(|task| task.id)
+            .collect::<Vec<_>>();
+        for id in completed
+            .iter()
+            .take(completed.len().saturating_sub(MAX_COMPLETED))
+        {
+            self.remove_task(*id);
+        }
+    }
+
+    fn save(&self) {
+        let downloads = self
+            .tasks
+            .peek()
+            .iter()
+            .filter_map(|task| {
+                let progress = task.progress.peek();
+                (!progress.state.is_completed()).then(|| SavedTask {
+                    name: task.name.clone(),
+                    url: task.url.clone(),
+                    target: to_stored_path(&task.target),
+                    state: progress.state.clone(),
+                    total: progress.total,
+                })
+            })
+            .collect::<Vec<_>>();
+        let saved = SavedQueue {
+            downloads,
+            installs: self.installs.borrow().clone(),
+        };
+
+        match serde_json::to_vec_pretty(&saved) {
+            Ok(data) => {
+                if let Err(e) =
+                    paths().and_then(|paths| write_atomic(&paths.download_queue(), &data))
+                {
+                    println!("Error when saving the download queue: {e}");
+                }
+            }
+            Err(e) => println!("Error when serializing the download queue: {e}"),
+        }
+    }
+
+    async fn run(
+        &self,
+        task: &DownloadTask,
+        total: Option<u64>,
+        ranges: bool,
+    ) -> Result<(), String> {
+        let (url, target) = (task.url.as_str(), task.target.as_path());
+        if let Some(parent) = target.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .map_err(|e| format!("Cannot create directory {parent:?}: {e}"))?;
+        }
+
+        let meter = Meter::new(task.progress);
+        let result = match total {
+            Some(total) if ranges && total >= MIN_SEGMENT_SIZE * 2 => {
+                self.download_segmented(url, target, total, &meter).await
+            }
+            _ => {
+                self.download_single(url, target, total, ranges, &meter)
+                    .await
+            }
+        };
+        meter.publish();
+
+        result
+    }
+
+    /// Size of the file and whether the server accepts Range requests for it.
+    async fn probe(&self, url: &str) -> (Option<u64>, bool) {
+        let Ok(response) = self.client.head(url).send().await else {
+            return (None, false);
+        };
+        if !response.status().is_success() {
+            return (None, false);
+        }
+
+        let headers = response.headers();
+        let total = headers
+            .get(CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse().ok());
+        let ranges = headers
+            .get(ACCEPT_RANGES)
+            .is_some_and(|value| value.as_bytes().eq_ignore_ascii_case(b"bytes"));
+
+        (total, ranges)
+    }
+
+    async fn download_single(
+        &self,
+        url: &str,
+        target: &Path,
+        total: Option<u64>,
+        resumable: bool,
+        meter: &Meter,
+    ) -> Result<(), String> {
+        let part_path = part_path(target, None);
+        let part_len = file_len(&part_path).await;
+        // Its next byte would be out of range, so there's nothing to request
+        if resumable && part_state(part_len, total) == PartState::Complete {
+            meter.add(part_len);
+        } else {
+            self.download_range(url, &part_path, 0, None, resumable, meter)
+                .await?;
+        }
+
+        tokio::fs::rename(&part_path, target)
+            .await
+            .map_err(|e| format!("Failed to move {part_path:?} to {target:?}: {e}"))
+    }
+
+    /// Every connection downloads its own part file, so each one can resume
+    /// on its own. They're joined once all of them are complete.
+    async fn download_segmented(
+        &self,
+        url: &str,
+        target: &Path,
+        total: u64,
+        meter: &Meter,
+    ) -> Result<(), String> {
+        let count = (total / MIN_SEGMENT_SIZE).clamp(1, CONNECTIONS_PER_FILE);
+        let segment_size = total.div_ceil(count);
+        let segments = (0..count)
+            .map(|index| {
+                let start = index * segment_size;
+                let end = (start + segment_size).min(total);
+                (part_path(target, Some(index)), start, end)
+            })
+            .collect::<Vec<_>>();
+
+        let results = futures::future::join_all(segments.iter().map(|(path, start, end)| {
+            self.download_range(url, path, *start, Some(*end), true, meter)
+        }))
+        .await;
+        results.into_iter().collect::<Result<Vec<_>, _>>()?;
+
+        for (path, start, end) in &segments {
+            if file_len(path).await != end - start {
+                let _ = tokio::fs::remove_file(path).await;
+                return Err(format!("Partial download {path:?} has the wrong size"));
+            }
+        }
+
+        let (first, ..) = &segments[0];
+        let mut output = tokio::fs::OpenOptions::new()
+            .append(true)
+            .open(first)
+            .await
+            .map_err(|e| format!("Failed to open {first:?}: {e}"))?;
+        for (path, ..) in &segments[1..] {
+            let mut input = tokio::fs::File::open(path)
+                .await
+                .map_err(|e| format!("Failed to open {path:?}: {e}"))?;
+            tokio::io::copy(&mut input, &mut output)
+                .await
+                .map_err(|e| format!("Failed to join {path:?}: {e}"))?;
+            let _ = tokio::fs::remove_file(path).await;
+        }
+        output
+            .flush()
+            .await
+            .map_err(|e| format!("Failed to write {first:?}: {e}"))?;
+
+        tokio::fs::rename(first, target)
+            .await
+            .map_err(|e| format!("Failed to move {first:?} to {target:?}: {e}"))
+    }
+
+    /// Downloads bytes `start..end` of `url` into `path`, retrying from where
+    /// the previous attempt stopped when the connection drops.
+    async fn download_range(
+        &self,
+        url: &str,
+        path: &Path,
+        start: u64,
+        end: Option<u64>,
+        resumable: bool,
+        meter: &Meter,
+    ) -> Result<(), String> {
+        if resumable {
+            meter.add(file_len(path).await);
+        }
+
+        let mut attempt = 0;
+        loop {
+            match self
+                .fetch_range(url, path, start, end, resumable, meter)
+                .await
+            {
+                Ok(()) => return Ok(()),
+                Err(e) if resumable && attempt < MAX_RETRIES => {
+                    attempt += 1;
+                    println!("{e}, retrying ({attempt}/{MAX_RETRIES})..");
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    async fn fetch_range(
+        &self,
+        url: &str,
+        path: &Path,
+        start: u64,
+        end: Option<u64>,
+        resumable: bool,
+        meter: &Meter,
+    ) -> Result<(), String> {
+        let offset = if resumable {
+            let len = end.map(|end| end - start);
+            match part_state(file_len(path).await, len) {
+                PartState::Complete => return Ok(()),
+                PartState::Resume(offset) => offset,
+                PartState::Stale(offset) => {
+                    meter.sub(offset);
+                    let _ = tokio::fs::remove_file(path).await;
+                    0
+                }
+            }
+        } else {
+            0
+        };
+
+        let mut request = self.client.get(url);
+        let ranged = resumable && (offset > 0 || end.is_some());
+        if ranged {
+            let last = end.map(|end| (end - 1).to_string()).unwrap_or_default();
+            request = request.header(RANGE, format!("bytes={}-{last}", start + offset));
+        }
+
+        let mut response = request
+            .send()
+            .await
+            .map_err(|e| format!("Failed to download {url}: {e}"))?;
+        let status = response.status();
+
+        if status == StatusCode::RANGE_NOT_SATISFIABLE {
+            // Nothing is left after the end of the part, it holds the whole file
+            let size = response
+                .headers()
+                .get(CONTENT_RANGE)
+                .and_then(|value| value.to_str().ok())
+                .and_then(range_size);
+            if offset > 0 && size == Some(start + offset) {
+                return Ok(());
+            }
+
+            // Left over from a different file with the same name
+            meter.sub(offset);
+            let _ = tokio::fs::remove_file(path).await;
+            return Err(format!("Stale partial download {path:?} discarded"));
+        }
+        if !status.is_success() {
+            return Err(format!("Download failed with status: {status}"));
+        }
+
+        let mut file = if ranged && status == StatusCode::PARTIAL_CONTENT {
+            tokio::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .await
+        } else if ranged && end.is_some() {
+            return Err(format!("Server ignored the requested range for {url}"));
+        } else {
+            meter.sub(offset);
+            tokio::fs::File::create(path).await
+        }
+        .map_err(|e| format!("Failed to open {path:?}: {e}"))?;
+
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .map_err(|e| format!("Failed to download {url}: {e}"))?
+        {
+            file.write_all(&chunk)
+                .await
+                .map_err(|e| format!("Failed to write {path:?}: {e}"))?;
+            meter.add(chunk.len() as u64);
+
+            let limit = self.settings.peek().downloads.bandwidth_limit;
+            if let Some(limit) = limit.filter(|limit| *limit > 0) {
+                let delay = self.throttle.borrow_mut().delay(chunk.len() as u64, limit);
+                if !delay.is_zero() {
+                    tokio::time::sleep(delay).await;
+                }
+            }
+        }
+        // Written in the background otherwise, it has to be complete before
+        // the part is moved
+        file.flush()
+            .await
+            .map_err(|e| format!("Failed to write {path:?}: {e}"))?;
+
+        Ok(())
+    }
+}
+
+/// Spreads the bandwidth limit over every connection of every download.
+#[derive(Default)]
+struct Throttle {
+    since: Option<Instant>,
+    bytes: u64,
+}
+
+impl Throttle {
+    /// How long to wait after receiving `bytes` to stay under `limit` bytes
+    /// per second, averaged over one second windows.
+    fn delay(&mut self, bytes: u64, limit: u64) -> Duration {
+        let now = Instant::now();
+        let since = match self.since {
+            Some(since) if now - since < Duration::from_secs(1) => since,
+            _ => {
+                self.since = Some(now);
+                self.bytes = 0;
+                now
+            }
+        };
+        self.bytes += bytes;
+
+        Duration::from_secs_f64(self.bytes as f64 / limit as f64).saturating_sub(now - since)
+    }
+}
+
+/// Counts bytes from all connections of a download and publishes them to its
+/// progress signal, at most every `PROGRESS_INTERVAL`.
+struct Meter {
+    progress: Signal<DownloadProgress>,
+    downloaded: Cell<u64>,
+    sample: RefCell<(Instant, u64)>,
+}
+
+impl Meter {
+    fn new(progress: Signal<DownloadProgress>) -> Self {
+        Self {
+            progress,
+            downloaded: Cell::new(0),
+            sample: RefCell::new((Instant::now(), 0)),
+        }
+    }
+
+    fn add(&self, bytes: u64) {
+        self.downloaded.set(self.downloaded.get() + bytes);
+
+        let (sampled_at, _) = *self.sample.borrow();
+        if sampled_at.elapsed() >= PROGRESS_INTERVAL {
+            self.publish();
+        }
+    }
+
+    fn sub(&self, bytes: u64) {
+        self.downloaded
+            .set(self.downloaded.get().saturating_sub(bytes));
+    }
+
+    fn publish(&self) {
+        let downloaded = self.downloaded.get();
+        let mut sample = self.sample.borrow_mut();
+        let (sampled_at, sampled) = *sample;
+
+        let elapsed = sampled_at.elapsed().as_secs_f64();
+        let speed = if elapsed > 0.0 {
+            (downloaded.saturating_sub(sampled) as f64 / elapsed) as u64
+        } else {
+            0
+        };
+        *sample = (Instant::now(), downloaded);
+
+        let mut progress = self.progress;
+        let mut progress = progress.write();
+        progress.downloaded = downloaded;
+        progress.speed = speed;
+    }
+}
+
+/// What a `.part` file left by an earlier attempt means for the next one.
+#[derive(Debug, PartialEq)]
+enum PartState {
+    /// It already holds everything
+    Complete,
+    /// Continues after the bytes it holds
+    Resume(u64),
+    /// Longer than it can be, left over from a different file
+    Stale(u64),
+}
+
+/// `part_len` being the size of the `.part` file and `len` the size it ends
+/// up with, when it's known.
+fn part_state(part_len: u64, len: Option<u64>) -> PartState {
+    match len {
+        Some(len) if part_len > 0 && part_len == len => PartState::Complete,
+        Some(len) if part_len > len => PartState::Stale(part_len),
+        _ => PartState::Resume(part_len),
+    }
+}
+
+/// Size of the whole file from a `Content-Range` header, e.g. `bytes */1234`.
+fn range_size(content_range: &str) -> Option<u64> {
+    content_range.rsplit_once('/')?.1.trim().parse().ok()
+}
+
+fn part_path(target: &Path, segment: Option<u64>) -> PathBuf {
+    match segment {
+        Some(segment) => PathBuf::from(format!("{}.part{segment}", target.display())),
+        None => PathBuf::from(format!("{}.part", target.display())),
+    }
+}
+
+/// Removes the `.part` files of a download, including every segment.
+fn remove_parts(target: &Path) {
+    let (Some(parent), Some(name)) = (target.parent(), target.file_name()) else {
+        return;
+    };
+    let prefix = format!("{}.part", name.to_string_lossy());
+
+    let Ok(entries) = fs::read_dir(parent) else {
+        return;
+    };
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        if entry.file_name().to_string_lossy().starts_with(&prefix) {
+            let _ = fs::remove_file(entry.path());
+        }
+    }
+}
+
+fn load_queue() -> SavedQueue {
+    let Ok(data) = paths()
+        .and_then(|paths| fs::read_to_string(paths.download_queue()).map_err(|e| e.to_string()))
+    else {
+        return SavedQueue::default();
+    };
+
+    serde_json::from_str(&data)
+        .or_else(|e| {
+            // Written before installs were saved along
+            serde_json::from_str(&data)
+                .map(|downloads| SavedQueue {
+                    downloads,
+                    installs: Vec::new(),
+                })
+                .map_err(|_| e)
+        })
+        .unwrap_or_else(|e| {
+            println!("Error when loading the download queue, starting empty: {e}");
+            SavedQueue::default()
+        })
+}
+
+async fn file_len(path: &Path) -> u64 {
+    tokio::fs::metadata(path)
+        .await
+        .map(|metadata| metadata.len())
+        .unwrap_or_default()
+}
+
+/// Downloads `url` to `target` through the app's download manager.
+pub async fn download_file(url: &str, target: &Path) -> Result<(), String> {
+    consume_context::<DownloadManager>()
+        .download(url, target)
+        .await
+}
+
+/// Human readable size, e.g. `1.5 GiB`.
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{bytes} {}", UNITS[0])
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
+
+/// Whether the file exists and has the expected md5, compared case-insensitively.
+pub fn md5_matches(path: &Path, expected: &str) -> Result<bool, String> {
+    let Ok(mut file) = fs::File::open(path) else {
+        return Ok(false);
+    };
+
+    let mut hasher = Md5::new();
+    let mut buffer = vec![0; 1024 * 1024];
+    loop {
+        let read = file
+            .read(&mut buffer)
+            .map_err(|e| format!("Failed to read {path:?}: {e}"))?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+
+    let hash = format!("{:x}", hasher.finalize());
+
+    Ok(hash.eq_ignore_ascii_case(expected))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn throttle_waits_for_the_bytes_over_the_limit() {
+        let mut throttle = Throttle::default();
+
+        let first = throttle.delay(500, 1000);
+        assert!(first > Duration::from_millis(400) && first <= Duration::from_millis(500));
+        // The window keeps counting, so the second half waits until its end
+        let second = throttle.delay(500, 1000);
+        assert!(second > Duration::from_millis(900) && second <= Duration::from_secs(1));
+    }
+
+    #[test]
+    fn throttle_starts_a_new_window_after_a_second() {
+        let mut throttle = Throttle {
+            since: Some(Instant::now() - Duration::from_secs(2)),
+            bytes: 10_000,
+        };
+
+        assert!(throttle.delay(100, 1000) <= Duration::from_millis(100));
+        assert_eq!(throttle.bytes, 100);
+    }
+
+    #[test]
+    fn formats_bytes_with_binary_units() {
+        assert_eq!(format_bytes(0), "0 B");
+        assert_eq!(format_bytes(1023), "1023 B");
+        assert_eq!(format_bytes(1536), "1.5 KiB");
+        assert_eq!(format_bytes(5 * 1024 * 1024 * 1024), "5.0 GiB");
+        assert_eq!(format_bytes(u64::MAX), "16777216.0 TiB");
+    }
+
+    #[test]
+    fn part_files_resume_finish_or_start_over() {
+        assert_eq!(part_state(0, Some(100)), PartState::Resume(0));
+        assert_eq!(part_state(40, Some(100)), PartState::Resume(40));
+        assert_eq!(part_state(100, Some(100)), PartState::Complete);
+        assert_eq!(part_state(140, Some(100)), PartState::Stale(140));
+        // Without a length there's nothing to compare with
+        assert_eq!(part_state(40, None), PartState::Resume(40));
+        // An empty file still needs its request
+        assert_eq!(part_state(0, Some(0)), PartState::Resume(0));
+    }
+
+    #[test]
+    fn reads_the_size_of_content_ranges() {
+        assert_eq!(range_size("bytes */1234"), Some(1234));
+        assert_eq!(range_size("bytes 0-9/1234"), Some(1234));
+        assert_eq!(range_size("bytes 0-9/*"), None);
+        assert_eq!(range_size("garbage"), None);
+    }
+
+    #[test]
+    fn removes_every_part_of_a_download() {
+        let dir = std::env::temp_dir().join(format!("elysia-parts-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let target = dir.join("game.zip");
+        for path in [
+            part_path(&target, None),
+            part_path(&target, Some(0)),
+            part_path(&target, Some(3)),
+        ] {
+            fs::write(path, "partial").unwrap();
+        }
+        fs::write(dir.join("other.zip.part"), "partial").unwrap();
+
+        remove_parts(&target);
+        let mut left = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+        left.sort();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(left, ["other.zip.part"]);
+    }
+}
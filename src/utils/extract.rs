@@ -0,0 +1,734 @@
+//! Archive extraction shared by game packages, runners and umu.
+//!
+//! Handles `.zip` and `.7z` (also split in `.001`, `.002`.. volumes) and
+//! `.tar`, `.tar.gz`, `.tar.xz` and `.tar.zst`. Archives are read as a stream
+//! so volumes never have to be joined on disk, and progress is counted in
+//! archive bytes read, which works the same for every format. The format is
+//! recognized by the first bytes of the archive, the file name only decides
+//! for the rare ones that don't start with anything recognizable.
+//!
+//! Nothing is written outside of the destination: entries with absolute paths
+//! or `..` are refused, and so are symlinks pointing out of it.
+
+use std::{
+    fs,
+    io::{self, Read, Seek, SeekFrom},
+    path::{Component, Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+};
+
+use crate::utils::filesystem::ensure_dir;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Zip,
+    SevenZip,
+    Tar,
+    TarGz,
+    TarXz,
+    TarZst,
+}
+
+impl Format {
+    /// Recognizes an archive by its first bytes, compressed ones are expected
+    /// to hold a tar archive.
+    fn sniff(header: &[u8]) -> Option<Format> {
+        [
+            (b"PK\x03\x04".as_slice(), Format::Zip),
+            // An empty zip archive
+            (b"PK\x05\x06", Format::Zip),
+            (b"7z\xBC\xAF\x27\x1C", Format::SevenZip),
+            (b"\x1F\x8B", Format::TarGz),
+            (b"\xFD7zXZ\x00", Format::TarXz),
+            (b"\x28\xB5\x2F\xFD", Format::TarZst),
+        ]
+        .into_iter()
+        .find(|(magic, _)| header.starts_with(magic))
+        .map(|(_, format)| format)
+        .or_else(|| (header.get(257..262) == Some(b"ustar")).then_some(Format::Tar))
+    }
+
+    fn detect(path: &Path) -> Option<Format> {
+        let name = path.file_name()?.to_string_lossy().to_lowercase();
+        // Volumes are named after the archive they belong to
+        let name = name.trim_end_matches(|c: char| c.is_ascii_digit());
+        let name = name.strip_suffix('.').unwrap_or(name);
+
+        [
+            (".zip", Format::Zip),
+            (".7z", Format::SevenZip),
+            (".tar", Format::Tar),
+            (".tar.gz", Format::TarGz),
+            (".tgz", Format::TarGz),
+            (".tar.xz", Format::TarXz),
+            (".txz", Format::TarXz),
+            (".tar.zst", Format::TarZst),
+            (".tzst", Format::TarZst),
+        ]
+        .into_iter()
+        .find(|(extension, _)| name.ends_with(extension))
+        .map(|(_, format)| format)
+    }
+}
+
+/// Progress and cancellation of an extraction, shared with the thread doing
+/// the work.
+#[derive(Debug, Clone, Default)]
+pub struct Extraction {
+    read: Arc<AtomicU64>,
+    total: Arc<AtomicU64>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Extraction {
+    /// Archive bytes read so far and the size of the archive.
+    pub fn progress(&self) -> (u64, u64) {
+        let total = self.total.load(Ordering::Relaxed);
+
+        (self.read.load(Ordering::Relaxed).min(total), total)
+    }
+
+    /// Stops the extraction at the next read, files already written stay.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Extracts `volumes`, the parts of one archive in order, to `destination`.
+    pub async fn run(&self, volumes: &[PathBuf], destination: &Path) -> Result<(), String> {
+        let extraction = self.clone();
+        let volumes = volumes.to_vec();
+        let destination = destination.to_path_buf();
+
+        tokio::task::spawn_blocking(move || extraction.run_blocking(&volumes, &destination))
+            .await
+            .map_err(|e| format!("Extraction thread failed: {e}"))?
+    }
+
+    fn run_blocking(&self, volumes: &[PathBuf], destination: &Path) -> Result<(), String> {
+        let first = volumes
+            .first()
+            .ok_or_else(|| "Nothing to extract".to_string())?;
+        let mut reader = Volumes::open(volumes)?;
+        let format = Format::sniff(&reader.header()?)
+            .or_else(|| Format::detect(first))
+            .ok_or_else(|| format!("Unsupported archive: {first:?}"))?;
+        ensure_dir(destination)?;
+
+        let len = reader.len;
+        self.total.store(len, Ordering::Relaxed);
+        self.read.store(0, Ordering::Relaxed);
+        let reader = Counting {
+            inner: reader,
+            extraction: self,
+        };
+
+        match format {
+            Format::Zip => extract_zip(reader, destination),
+            Format::SevenZip => extract_7z(reader, len, destination),
+            Format::Tar => extract_tar(reader, destination),
+            Format::TarGz => extract_tar(flate2::read::GzDecoder::new(reader), destination),
+            Format::TarXz => extract_tar(xz2::read::XzDecoder::new(reader), destination),
+            Format::TarZst => extract_tar(
+                zstd::stream::read::Decoder::new(reader)
+                    .map_err(|e| format!("Cannot read {first:?}: {e}"))?,
+                destination,
+            ),
+        }
+        .map_err(|e| {
+            if self.cancelled.load(Ordering::Relaxed) {
+                "Extraction cancelled".to_string()
+            } else {
+                format!("Failed to extract {first:?} to {destination:?}: {e}")
+            }
+        })
+    }
+}
+
+/// Extracts an archive with no way to follow or cancel it.
+pub async fn extract(volumes: &[PathBuf], destination: &Path) -> Result<(), String> {
+    Extraction::default().run(volumes, destination).await
+}
+
+fn extract_tar(reader: impl Read, destination: &Path) -> Result<(), String> {
+    let mut archive = tar::Archive::new(reader);
+    archive.set_preserve_mtime(true);
+
+    for entry in archive.entries().map_err(|e| e.to_string())? {
+        let mut entry = entry.map_err(|e| e.to_string())?;
+        let name = entry.path().map_err(|e| e.to_string())?.into_owned();
+        let path = enclosed_path(destination, &name)
+            .ok_or_else(|| format!("Refusing to write outside of destination: {name:?}"))?;
+
+        if entry.header().entry_type().is_symlink() {
+            let target = entry
+                .link_name()
+                .map_err(|e| e.to_string())?
+                .unwrap_or_default();
+            if !link_stays_inside(destination, &path, &target)? {
+                return Err(format!(
+                    "Refusing to link outside of destination: {name:?} -> {target:?}"
+                ));
+            }
+        }
+
+        // Also checks that no symlink leads the entry out of `destination`,
+        // and applies its unix mode
+        entry.unpack_in(destination).map_err(|e| e.to_string())?;
+    }
+
+    Ok(())
+}
+
+fn extract_zip(reader: impl Read + Seek, destination: &Path) -> Result<(), String> {
+    let mut archive = zip::ZipArchive::new(reader).map_err(|e| e.to_string())?;
+
+    for index in 0..archive.len() {
+        let mut file = archive.by_index(index).map_err(|e| e.to_string())?;
+        let relative = file
+            .enclosed_name()
+            .ok_or_else(|| format!("Refusing to write outside of destination: {}", file.name()))?;
+        let path = destination.join(relative);
+
+        if file.is_dir() {
+            ensure_dir(&path)?;
+            continue;
+        }
+        if let Some(parent) = path.parent() {
+            ensure_dir(parent)?;
+        }
+        if file.is_symlink() {
+            let mut target = String::new();
+            file.read_to_string(&mut target)
+                .map_err(|e| format!("Cannot read link {}: {e}", file.name()))?;
+            if !link_stays_inside(destination, &path, Path::new(&target))? {
+                return Err(format!(
+                    "Refusing to link outside of destination: {} -> {target}",
+                    file.name()
+                ));
+            }
+            create_symlink(&path, Path::new(&target))?;
+            continue;
+        }
+
+        let mut output =
+            fs::File::create(&path).map_err(|e| format!("Cannot create {path:?}: {e}"))?;
+        io::copy(&mut file, &mut output).map_err(|e| e.to_string())?;
+        set_mode(&path, file.unix_mode())?;
+    }
+
+    Ok(())
+}
+
+fn extract_7z(reader: impl Read + Seek, len: u64, destination: &Path) -> Result<(), String> {
+    let mut archive = sevenz_rust::SevenZReader::new(reader, len, sevenz_rust::Password::empty())
+        .map_err(|e| e.to_string())?;
+
+    let mut result = Ok(());
+    archive
+        .for_each_entries(|entry, data| {
+            let Some(path) = enclosed_path(destination, Path::new(entry.name())) else {
+                result = Err(format!(
+                    "Refusing to write outside of destination: {}",
+                    entry.name()
+                ));
+                return Ok(false);
+            };
+
+            if entry.is_directory() {
+                ensure_dir(&path).map_err(sevenz_rust::Error::other)?;
+                return Ok(true);
+            }
+            if let Some(parent) = path.parent() {
+                ensure_dir(parent).map_err(sevenz_rust::Error::other)?;
+            }
+
+            let mut output = fs::File::create(&path).map_err(sevenz_rust::Error::io)?;
+            io::copy(data, &mut output).map_err(sevenz_rust::Error::io)?;
+            // p7zip stores the unix mode in the high bits of the attributes
+            let attributes = entry.windows_attributes();
+            if attributes & 0x8000 != 0 {
+                set_mode(&path, Some(attributes >> 16)).map_err(sevenz_rust::Error::other)?;
+            }
+
+            Ok(true)
+        })
+        .map_err(|e| e.to_string())?;
+
+    result
+}
+
+/// `name` inside `destination`, unless it's absolute or goes up with `..`.
+fn enclosed_path(destination: &Path, name: &Path) -> Option<PathBuf> {
+    if name
+        .components()
+        .any(|component| !matches!(component, Component::Normal(_) | Component::CurDir))
+    {
+        return None;
+    }
+
+    Some(destination.join(name))
+}
+
+/// Whether a symlink at `link` to `target` points inside of `destination`.
+/// Only relative targets qualify, and their `..` have to come first, as one
+/// after a name could go up from wherever a symlink by that name points to.
+fn link_stays_inside(destination: &Path, link: &Path, target: &Path) -> Result<bool, String> {
+    let root = destination
+        .canonicalize()
+        .map_err(|e| format!("Cannot resolve {destination:?}: {e}"))?;
+    let Some(parent) = link.parent() else {
+        return Ok(false);
+    };
+    ensure_dir(parent)?;
+    // Directories of the archive may be symlinks themselves
+    let mut resolved = parent
+        .canonicalize()
+        .map_err(|e| format!("Cannot resolve {parent:?}: {e}"))?;
+
+    let mut descending = false;
+    for component in target.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if !descending => {
+                resolved.pop();
+            }
+            Component::Normal(_) => descending = true,
+            _ => return Ok(false),
+        }
+    }
+
+    Ok(resolved.starts_with(&root))
+}
+
+#[cfg(unix)]
+fn create_symlink(path: &Path, target: &Path) -> Result<(), String> {
+    std::os::unix::fs::symlink(target, path)
+        .map_err(|e| format!("Cannot create link {path:?}: {e}"))
+}
+
+#[cfg(not(unix))]
+fn create_symlink(path: &Path, target: &Path) -> Result<(), String> {
+    // Written as a file with the target in it, like the archive stores it
+    fs::write(path, target.to_string_lossy().as_bytes())
+        .map_err(|e| format!("Cannot create {path:?}: {e}"))
+}
+
+#[cfg(unix)]
+fn set_mode(path: &Path, mode: Option<u32>) -> Result<(), String> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let Some(mode) = mode else {
+        return Ok(());
+    };
+
+    fs::set_permissions(path, fs::Permissions::from_mode(mode & 0o7777))
+        .map_err(|e| format!("Cannot set permissions of {path:?}: {e}"))
+}
+
+#[cfg(not(unix))]
+fn set_mode(_path: &Path, _mode: Option<u32>) -> Result<(), String> {
+    Ok(())
+}
+
+/// Reads the volumes of an archive as one file.
+struct Volumes {
+    files: Vec<(fs::File, u64)>,
+    len: u64,
+    position: u64,
+}
+
+impl Volumes {
+    fn open(paths: &[PathBuf]) -> Result<Self, String> {
+        let files = paths
+            .iter()
+            .map(|path| {
+                let file =
+                    fs::File::open(path).map_err(|e| format!("Cannot open {path:?}: {e}"))?;
+                let len = file
+                    .metadata()
+                    .map_err(|e| format!("Cannot read {path:?}: {e}"))?
+                    .len();
+                Ok((file, len))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        let len = files.iter().map(|(_, len)| len).sum();
+
+        Ok(Self {
+            files,
+            len,
+            position: 0,
+        })
+    }
+
+    /// The first bytes of the archive, for `Format::sniff`.
+    fn header(&mut self) -> Result<Vec<u8>, String> {
+        let mut header = Vec::new();
+        self.take(512)
+            .read_to_end(&mut header)
+            .map_err(|e| format!("Cannot read archive: {e}"))?;
+        self.position = 0;
+
+        Ok(header)
+    }
+}
+
+impl Read for Volumes {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        let mut start = 0;
+        for (file, len) in &mut self.files {
+            if self.position < start + *len {
+                file.seek(SeekFrom::Start(self.position - start))?;
+                let read = file.read(buffer)?;
+                self.position += read as u64;
+                return Ok(read);
+            }
+            start += *len;
+        }
+
+        Ok(0)
+    }
+}
+
+impl Seek for Volumes {
+    fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+        let position = match position {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.len.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        };
+        self.position =
+            position.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Invalid seek"))?;
+
+        Ok(self.position)
+    }
+}
+
+/// Counts the bytes read for progress, and fails reads once cancelled.
+struct Counting<'a, R> {
+    inner: R,
+    extraction: &'a Extraction,
+}
+
+impl<R: Read> Read for Counting<'_, R> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        if self.extraction.cancelled.load(Ordering::Relaxed) {
+            // Not `Interrupted`, readers retry those
+            return Err(io::Error::other("Extraction cancelled"));
+        }
+
+        let read = self.inner.read(buffer)?;
+        self.extraction
+            .read
+            .fetch_add(read as u64, Ordering::Relaxed);
+
+        Ok(read)
+    }
+}
+
+impl<R: Seek> Seek for Counting<'_, R> {
+    fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+        self.inner.seek(position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("elysia-extract-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        dir
+    }
+
+    /// Writes a tar archive, names are written as they are since `tar` won't
+    /// build the broken ones.
+    fn write_tar(path: &Path, entries: &[(&str, tar::EntryType, &str)]) {
+        let mut builder = tar::Builder::new(fs::File::create(path).unwrap());
+        for (name, kind, content) in entries {
+            let mut header = tar::Header::new_gnu();
+            header.as_old_mut().name[..name.len()].copy_from_slice(name.as_bytes());
+            let data = if kind.is_symlink() {
+                header.as_old_mut().linkname[..content.len()].copy_from_slice(content.as_bytes());
+                ""
+            } else {
+                content
+            };
+            header.set_entry_type(*kind);
+            header.set_size(data.len() as u64);
+            header.set_mode(0o755);
+            header.set_cksum();
+            builder.append(&header, data.as_bytes()).unwrap();
+        }
+        builder.finish().unwrap();
+    }
+
+    /// Entries with content, or symlinks to a target.
+    fn write_zip(path: &Path, files: &[(&str, &str)], links: &[(&str, &str)]) {
+        let mut zip = zip::ZipWriter::new(fs::File::create(path).unwrap());
+        let options = zip::write::SimpleFileOptions::default().unix_permissions(0o755);
+        for (name, content) in files {
+            zip.start_file(*name, options).unwrap();
+            zip.write_all(content.as_bytes()).unwrap();
+        }
+        for (name, target) in links {
+            zip.add_symlink(*name, *target, options).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    fn extract(archive: &Path, destination: &Path) -> Result<(), String> {
+        Extraction::default().run_blocking(&[archive.to_path_buf()], destination)
+    }
+
+    #[test]
+    fn tar_refuses_parent_entries() {
+        let dir = temp_dir("tar-parent");
+        let archive = dir.join("archive.tar");
+        write_tar(
+            &archive,
+            &[("../escaped.txt", tar::EntryType::Regular, "data")],
+        );
+
+        assert!(extract(&archive, &dir.join("out")).is_err());
+        assert!(!dir.join("escaped.txt").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn tar_refuses_absolute_entries() {
+        let dir = temp_dir("tar-absolute");
+        let archive = dir.join("archive.tar");
+        let escaped = dir.join("escaped.txt");
+        write_tar(
+            &archive,
+            &[(&escaped.to_string_lossy(), tar::EntryType::Regular, "data")],
+        );
+
+        assert!(extract(&archive, &dir.join("out")).is_err());
+        assert!(!escaped.exists());
+        assert!(
+            !dir.join("out")
+                .join(escaped.strip_prefix("/").unwrap())
+                .exists()
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn tar_refuses_links_out_of_destination() {
+        let dir = temp_dir("tar-link");
+        for (name, target) in [("up", ".."), ("etc", "/etc"), ("sneaky", "bin/../..")] {
+            let archive = dir.join(format!("{name}.tar"));
+            write_tar(&archive, &[(name, tar::EntryType::Symlink, target)]);
+
+            let out = dir.join(format!("out-{name}"));
+            assert!(extract(&archive, &out).is_err(), "{name} -> {target}");
+            assert!(fs::symlink_metadata(out.join(name)).is_err());
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn tar_keeps_links_and_modes_inside_destination() {
+        let dir = temp_dir("tar-inside");
+        let archive = dir.join("archive.tar");
+        write_tar(
+            &archive,
+            &[
+                ("bin/wine64", tar::EntryType::Regular, "wine"),
+                ("bin/wine", tar::EntryType::Symlink, "wine64"),
+                ("lib/bin", tar::EntryType::Symlink, "../bin"),
+            ],
+        );
+        let out = dir.join("out");
+
+        extract(&archive, &out).unwrap();
+        assert_eq!(fs::read_to_string(out.join("bin/wine")).unwrap(), "wine");
+        assert_eq!(
+            fs::read_to_string(out.join("lib/bin/wine64")).unwrap(),
+            "wine"
+        );
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(out.join("bin/wine64"))
+                .unwrap()
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o755);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn zip_refuses_parent_and_absolute_entries() {
+        let dir = temp_dir("zip-escape");
+        let escaped = dir.join("absolute.txt");
+        for (name, entry) in [
+            ("parent", "../parent.txt".to_string()),
+            ("absolute", escaped.to_string_lossy().into_owned()),
+        ] {
+            let archive = dir.join(format!("{name}.zip"));
+            write_zip(&archive, &[(&entry, "data")], &[]);
+
+            assert!(extract(&archive, &dir.join("out")).is_err(), "{entry}");
+        }
+        assert!(!dir.join("parent.txt").exists());
+        assert!(!escaped.exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn zip_refuses_links_out_of_destination() {
+        let dir = temp_dir("zip-link");
+        for (name, links) in [
+            ("up", vec![("up", "..")]),
+            ("etc", vec![("etc", "/etc")]),
+            // `here/up` looks like it stays inside, but `here` is the
+            // destination itself
+            ("chained", vec![("here", "."), ("here/up", "..")]),
+        ] {
+            let archive = dir.join(format!("{name}.zip"));
+            write_zip(&archive, &[], &links);
+
+            let out = dir.join(format!("out-{name}"));
+            let (escaping, _) = links[links.len() - 1];
+            assert!(extract(&archive, &out).is_err(), "{name}");
+            assert!(fs::symlink_metadata(out.join(escaping)).is_err());
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn zip_keeps_links_inside_destination() {
+        let dir = temp_dir("zip-inside");
+        let archive = dir.join("archive.zip");
+        write_zip(
+            &archive,
+            &[("files/umu-run", "umu")],
+            &[("umu-run", "files/umu-run")],
+        );
+        let out = dir.join("out");
+
+        extract(&archive, &out).unwrap();
+        assert!(
+            fs::symlink_metadata(out.join("umu-run"))
+                .unwrap()
+                .is_symlink()
+        );
+        assert_eq!(fs::read_to_string(out.join("umu-run")).unwrap(), "umu");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn recognizes_archives_by_their_first_bytes() {
+        let mut tar = vec![0; 512];
+        tar[257..263].copy_from_slice(b"ustar\0");
+
+        assert_eq!(Format::sniff(b"PK\x03\x04rest"), Some(Format::Zip));
+        assert_eq!(
+            Format::sniff(b"7z\xBC\xAF\x27\x1C\x00\x04"),
+            Some(Format::SevenZip)
+        );
+        assert_eq!(Format::sniff(b"\x1F\x8B\x08\x00"), Some(Format::TarGz));
+        assert_eq!(Format::sniff(b"\xFD7zXZ\x00\x00"), Some(Format::TarXz));
+        assert_eq!(Format::sniff(b"\x28\xB5\x2F\xFD\x04"), Some(Format::TarZst));
+        assert_eq!(Format::sniff(&tar), Some(Format::Tar));
+        assert_eq!(Format::sniff(b"plain text"), None);
+    }
+
+    #[test]
+    fn names_only_decide_without_known_first_bytes() {
+        assert_eq!(Format::detect(Path::new("game.zip.001")), Some(Format::Zip));
+        assert_eq!(
+            Format::detect(Path::new("wine.TAR.XZ")),
+            Some(Format::TarXz)
+        );
+        assert_eq!(Format::detect(Path::new("readme.txt")), None);
+
+        // A gzipped tarball under a misleading name
+        let dir = temp_dir("sniff");
+        let archive = dir.join("archive.zip");
+        let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(
+            fs::File::create(&archive).unwrap(),
+            flate2::Compression::fast(),
+        ));
+        let mut header = tar::Header::new_gnu();
+        header.set_size(4);
+        header.set_mode(0o644);
+        builder
+            .append_data(&mut header, "file.txt", "data".as_bytes())
+            .unwrap();
+        builder.into_inner().unwrap().finish().unwrap();
+
+        extract(&archive, &dir.join("out")).unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.join("out/file.txt")).unwrap(),
+            "data"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn extracts_volumes_as_one_archive() {
+        let dir = temp_dir("volumes");
+        let archive = dir.join("whole.zip");
+        let content = "game data ".repeat(200);
+        write_zip(
+            &archive,
+            &[("first.txt", &content), ("second/file.txt", "second")],
+            &[],
+        );
+
+        // Split at odd sizes, so entries and headers cross volumes
+        let data = fs::read(&archive).unwrap();
+        let volumes = data
+            .chunks(data.len() / 3 + 1)
+            .enumerate()
+            .map(|(index, chunk)| {
+                let path = dir.join(format!("game.zip.{:03}", index + 1));
+                fs::write(&path, chunk).unwrap();
+                path
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(volumes.len(), 3);
+        let out = dir.join("out");
+
+        let extraction = Extraction::default();
+        extraction.run_blocking(&volumes, &out).unwrap();
+        assert_eq!(fs::read_to_string(out.join("first.txt")).unwrap(), content);
+        assert_eq!(
+            fs::read_to_string(out.join("second/file.txt")).unwrap(),
+            "second"
+        );
+        let (read, total) = extraction.progress();
+        assert_eq!(total, data.len() as u64);
+        assert!(read > 0 && read <= total);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn cancelled_extractions_stop() {
+        let dir = temp_dir("cancel");
+        let archive = dir.join("archive.tar");
+        write_tar(&archive, &[("file.txt", tar::EntryType::Regular, "data")]);
+
+        let extraction = Extraction::default();
+        extraction.cancel();
+        assert_eq!(
+            extraction.run_blocking(&[archive], &dir.join("out")),
+            Err("Extraction cancelled".to_string())
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,4 +1,8 @@
-use std::{fs, path::Path};
+use std::{
+    fs,
+    io::{ErrorKind, Write},
+    path::Path,
+};
 
 pub fn ensure_dir(dir: &Path) -> Result<(), String> {
     let exists =
@@ -22,13 +26,150 @@ pub fn ensure_writable(path: &Path) -> Result<(), String> {
     Ok(())
 }
 
-pub fn ensure_or_default<'a>(path: &'a Path, default: &Path) -> Result<&'a Path, String> {
-    match ensure_dir(path) {
-        Ok(()) => Ok(path),
-        Err(e) => {
-            println!("Error when creating/loading directory from config, will use default: {e}");
-            ensure_dir(default)?;
-            Ok(path)
+/// Replaces `path` with `data` so it's either fully written or untouched,
+/// even when the process or the system dies halfway.
+pub fn write_atomic(path: &Path, data: &[u8]) -> Result<(), String> {
+    let mut temp_name = path.file_name().unwrap_or_default().to_os_string();
+    temp_name.push(".tmp");
+    let temp = path.with_file_name(temp_name);
+
+    let mut file = fs::File::create(&temp).map_err(|e| format!("Cannot create {temp:?}: {e}"))?;
+    file.write_all(data)
+        .and_then(|()| file.sync_all())
+        .map_err(|e| format!("Cannot write {temp:?}: {e}"))?;
+    fs::rename(&temp, path).map_err(|e| format!("Cannot replace {path:?}: {e}"))?;
+
+    // Makes the rename itself durable
+    #[cfg(unix)]
+    if let Some(parent) = path.parent()
+        && let Ok(directory) = fs::File::open(parent)
+    {
+        let _ = directory.sync_all();
+    }
+
+    Ok(())
+}
+
+/// Whether `path` can be used as a directory, without creating anything.
+pub fn check_directory(path: &Path) -> Result<(), String> {
+    if !path.is_absolute() {
+        return Err(format!("Not an absolute path: {path:?}"));
+    }
+    if path.exists() {
+        if !path.is_dir() {
+            return Err(format!("Not a directory: {path:?}"));
+        }
+        return ensure_writable(path);
+    }
+
+    // Created later, inside of the closest directory that exists
+    let parent = path
+        .ancestors()
+        .skip(1)
+        .find(|ancestor| ancestor.exists())
+        .ok_or_else(|| format!("No existing parent for {path:?}"))?;
+    if !parent.is_dir() {
+        return Err(format!("Not a directory: {parent:?}"));
+    }
+    ensure_writable(parent)
+}
+
+/// Moves everything in `from` into `to`, copying when they're on different
+/// filesystems. Fails before touching anything when an entry already exists
+/// in `to`.
+pub fn move_directory(from: &Path, to: &Path) -> Result<(), String> {
+    if !from.exists() || from == to {
+        return Ok(());
+    }
+    if to.starts_with(from) {
+        return Err(format!("Cannot move {from:?} inside of itself"));
+    }
+    ensure_dir(to)?;
+
+    let entries = fs::read_dir(from)
+        .map_err(|e| format!("Cannot read directory {from:?}: {e}"))?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name())
+        .collect::<Vec<_>>();
+    if let Some(name) = entries.iter().find(|name| to.join(name).exists()) {
+        return Err(format!("{:?} already exists", to.join(name)));
+    }
+
+    for name in entries {
+        let source = from.join(&name);
+        let destination = to.join(&name);
+        match fs::rename(&source, &destination) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::CrossesDevices => {
+                copy_recursive(&source, &destination)?;
+                remove_any(&source)?;
+            }
+            Err(e) => return Err(format!("Cannot move {source:?} to {destination:?}: {e}")),
+        }
+    }
+
+    // Only gone when nothing else was put there in the meantime
+    let _ = fs::remove_dir(from);
+
+    Ok(())
+}
+
+/// Moves a single file, copying it when it's on another filesystem.
+pub fn move_file(from: &Path, to: &Path) -> Result<(), String> {
+    if let Some(parent) = to.parent() {
+        ensure_dir(parent)?;
+    }
+
+    match fs::rename(from, to) {
+        Err(e) if e.kind() == ErrorKind::CrossesDevices => {
+            fs::copy(from, to).map_err(|e| format!("Cannot copy {from:?} to {to:?}: {e}"))?;
+            fs::remove_file(from).map_err(|e| format!("Cannot remove {from:?}: {e}"))
+        }
+        moved => moved.map_err(|e| format!("Cannot move {from:?} to {to:?}: {e}")),
+    }
+}
+
+fn copy_recursive(from: &Path, to: &Path) -> Result<(), String> {
+    let metadata = fs::symlink_metadata(from).map_err(|e| format!("Cannot read {from:?}: {e}"))?;
+
+    if metadata.is_symlink() {
+        let target = fs::read_link(from).map_err(|e| format!("Cannot read {from:?}: {e}"))?;
+        return symlink(&target, to);
+    }
+    if metadata.is_dir() {
+        ensure_dir(to)?;
+        for entry in fs::read_dir(from).map_err(|e| format!("Cannot read {from:?}: {e}"))? {
+            let entry = entry.map_err(|e| format!("Cannot read {from:?}: {e}"))?;
+            copy_recursive(&entry.path(), &to.join(entry.file_name()))?;
         }
+        return Ok(());
     }
+
+    fs::copy(from, to)
+        .map(|_| ())
+        .map_err(|e| format!("Cannot copy {from:?} to {to:?}: {e}"))
+}
+
+fn remove_any(path: &Path) -> Result<(), String> {
+    let is_dir = fs::symlink_metadata(path).is_ok_and(|metadata| metadata.is_dir());
+    let removed = if is_dir {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    };
+
+    removed.map_err(|e| format!("Cannot remove {path:?}: {e}"))
+}
+
+#[cfg(unix)]
+fn symlink(target: &Path, link: &Path) -> Result<(), String> {
+    std::os::unix::fs::symlink(target, link)
+        .map_err(|e| format!("Cannot link {link:?} to {target:?}: {e}"))
+}
+
+#[cfg(not(unix))]
+fn symlink(target: &Path, link: &Path) -> Result<(), String> {
+    fs::copy(target, link)
+        .map(|_| ())
+        .map_err(|e| format!("Cannot copy {target:?} to {link:?}: {e}"))
 }
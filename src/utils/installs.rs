@@ -0,0 +1,406 @@
+use std::{collections::HashMap, future::Future, path::PathBuf, time::Duration};
+
+use freya::prelude::*;
+
+use crate::{
+    context::Context,
+    game_providers::{
+        hoyoplay::{
+            api::{self, GameServerConfig},
+            get_channel_sdk, get_game_packages, get_game_plugins,
+            install::{self, Extra},
+        },
+        kuro,
+    },
+    runners::Runner,
+    settings::{Channel, GlobalSettings, InstalledGame},
+    utils::{
+        cache::CachePolicy,
+        download::{DownloadManager, InstallIntent},
+        extract::Extraction,
+    },
+};
+
+/// How often the unpacking progress of an install is published
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum InstallStage {
+    Running,
+    /// Archive bytes read and the archive size
+    Unpacking {
+        read: u64,
+        total: u64,
+    },
+    Failed(String),
+}
+
+impl InstallStage {
+    pub fn is_running(&self) -> bool {
+        !matches!(self, InstallStage::Failed(_))
+    }
+}
+
+/// What `Installs::cancel` needs to stop an install.
+struct RunningInstall {
+    task: Task,
+    extraction: Extraction,
+    /// Where its downloads go
+    directory: PathBuf,
+}
+
+/// Installs in progress, keyed by game id. They run in the scope `new` was
+/// called from, so they outlive the page that started them.
+#[derive(Clone, Copy, PartialEq)]
+pub struct Installs {
+    scope: ScopeId,
+    pub stages: Signal<HashMap<String, InstallStage>>,
+    running: Signal<HashMap<String, RunningInstall>>,
+}
+
+impl Default for Installs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Installs {
+    pub fn new() -> Self {
+        let scope = current_scope_id().unwrap_or(ScopeId::ROOT);
+
+        Self {
+            scope,
+            stages: Signal::new_in_scope(HashMap::new(), scope),
+            running: Signal::new_in_scope(HashMap::new(), scope),
+        }
+    }
+
+    pub fn stage(&self, game_id: &str) -> Option<InstallStage> {
+        self.stages.read().get(game_id).cloned()
+    }
+
+    pub fn is_running(&self, game_id: &str) -> bool {
+        self.stage(game_id).is_some_and(|stage| stage.is_running())
+    }
+
+    pub fn any_running(&self) -> bool {
+        self.stages.peek().values().any(InstallStage::is_running)
+    }
+
+    pub fn hoyoplay(
+        self,
+        game: api::Game,
+        server: Option<GameServerConfig>,
+        settings: Signal<GlobalSettings>,
+    ) {
+        let intent = InstallIntent {
+            game_id: game.id.clone(),
+            server: server.as_ref().map(|server| server.game_id.clone()),
+        };
+        let install_path = install_path(&settings.peek(), &game.id, &game.biz);
+        let temp_dir = settings.peek().temp_directory.join(&game.biz);
+        let extraction = Extraction::default();
+
+        let install = install_hoyoplay_game(
+            game,
+            server,
+            install_path,
+            temp_dir.clone(),
+            extraction.clone(),
+            settings,
+        );
+        self.start(intent, temp_dir, extraction, install);
+    }
+
+    pub fn kuro(self, game: kuro::Game, settings: Signal<GlobalSettings>) {
+        let intent = InstallIntent {
+            game_id: game.id.clone(),
+            server: None,
+        };
+        // Resources are downloaded straight into the game directory
+        let install_path = install_path(&settings.peek(), &game.id, &game.biz_name);
+
+        let install = install_kuro_game(game, install_path.clone(), settings);
+        self.start(intent, install_path, Extraction::default(), install);
+    }
+
+    /// Starts the installs interrupted by the last exit once their game shows
+    /// up in `ctx`, their downloads carry on where they stopped.
+    pub fn resume(self, ctx: &Context, settings: Signal<GlobalSettings>) {
+        let manager = consume_context::<DownloadManager>();
+
+        for intent in manager.pending_installs() {
+            if self.stages.peek().contains_key(&intent.game_id) {
+                continue;
+            }
+
+            if let Some(game) = ctx.api_games.iter().find(|g| g.id == intent.game_id) {
+                let server = game
+                    .game_server_configs
+                    .iter()
+                    .find(|server| Some(&server.game_id) == intent.server.as_ref())
+                    .cloned();
+                println!("Resuming the install of {}..", game.display.name);
+                self.hoyoplay(game.clone(), server, settings);
+            } else if let Some(game) = ctx.kuro_games.iter().find(|g| g.id == intent.game_id) {
+                println!("Resuming the install of {}..", game.name);
+                self.kuro(game.clone(), settings);
+            }
+        }
+    }
+
+    /// Stops an install along with its downloads and extraction. Files already
+    /// unpacked stay, the next install checks them.
+    pub fn cancel(self, game_id: &str) {
+        let mut running = self.running;
+        let Some(install) = running.write().remove(game_id) else {
+            return;
+        };
+
+        install.task.cancel();
+        install.extraction.cancel();
+
+        let manager = consume_context::<DownloadManager>();
+        manager.cancel_in(&install.directory);
+        manager.set_unpacking_in(&install.directory, None);
+        manager.end_install(game_id);
+
+        let mut stages = self.stages;
+        stages.write().remove(game_id);
+        println!("Cancelled the install of {game_id}");
+    }
+
+    fn start(
+        self,
+        intent: InstallIntent,
+        directory: PathBuf,
+        extraction: Extraction,
+        install: impl Future<Output = Result<(), String>> + 'static,
+    ) {
+        let game_id = intent.game_id.clone();
+        if self.is_running(&game_id) {
+            return;
+        }
+
+        let manager = consume_context::<DownloadManager>();
+        manager.begin_install(intent);
+
+        let mut stages = self.stages;
+        let mut running = self.running;
+        stages
+            .write()
+            .insert(game_id.clone(), InstallStage::Running);
+
+        let task = self.scope.push_future({
+            to_owned![manager, game_id, directory];
+            async move {
+                let result = install.await;
+                running.write().remove(&game_id);
+                manager.set_unpacking_in(&directory, None);
+                manager.end_install(&game_id);
+
+                match result {
+                    Ok(()) => {
+                        stages.write().remove(&game_id);
+                    }
+                    Err(e) => {
+                        println!("{e}");
+                        stages.write().insert(game_id, InstallStage::Failed(e));
+                    }
+                }
+            }
+        });
+        let Some(task) = task else {
+            return;
+        };
+
+        // Publishes the unpacking progress to the game page and the downloads
+        // being unpacked, until the install ends
+        self.scope.push_future({
+            to_owned![game_id, directory, extraction];
+            async move {
+                while running.peek().contains_key(&game_id) {
+                    let (read, total) = extraction.progress();
+                    let unpacking = (total > 0 && read < total).then_some((read, total));
+                    let stage = match unpacking {
+                        Some((read, total)) => InstallStage::Unpacking { read, total },
+                        None => InstallStage::Running,
+                    };
+                    if stages.peek().get(&game_id) != Some(&stage) {
+                        stages.write().insert(game_id.clone(), stage);
+                    }
+                    manager.set_unpacking_in(&directory, unpacking);
+
+                    tokio::time::sleep(PROGRESS_INTERVAL).await;
+                }
+            }
+        });
+
+        running.write().insert(
+            game_id,
+            RunningInstall {
+                task,
+                extraction,
+                directory,
+            },
+        );
+    }
+}
+
+/// Where a game is installed, or will be.
+fn install_path(settings: &GlobalSettings, game_id: &str, biz: &str) -> PathBuf {
+    settings
+        .installed_games
+        .get(game_id)
+        .map(|installed| installed.install_path.clone())
+        .unwrap_or_else(|| settings.games_directory.join(biz))
+}
+
+async fn install_hoyoplay_game(
+    game: api::Game,
+    server: Option<GameServerConfig>,
+    install_path: PathBuf,
+    temp_dir: PathBuf,
+    extraction: Extraction,
+    mut settings: Signal<GlobalSettings>,
+) -> Result<(), String> {
+    let installed = settings.peek().installed_games.get(&game.id).cloned();
+    let endpoints = settings.peek().endpoints.clone();
+    let package_id = server
+        .as_ref()
+        .map(|server| server.game_id.clone())
+        .unwrap_or_else(|| game.id.clone());
+    let name = game.display.name.clone();
+
+    let packages = get_game_packages(&package_id, CachePolicy::Bypass)
+        .await
+        .map_err(|e| format!("Failed to load packages for {name}: {e}"))?;
+    let Some(release) = packages.main.major else {
+        return Err(format!("No package available for {name}"));
+    };
+
+    let up_to_date = installed.as_ref().is_some_and(|installed| {
+        installed.version.as_ref() == Some(&release.version)
+            && installed.server.as_ref() == Some(&package_id)
+    });
+    if !up_to_date {
+        println!("Installing {name} {}..", release.version);
+        install::install_release(&release, &install_path, &temp_dir, &endpoints, &extraction)
+            .await
+            .map_err(|e| format!("Failed to install {name}: {e}"))?;
+
+        let executable_path = server
+            .as_ref()
+            .map(|server| server.executable())
+            .or_else(|| install::find_executable(&install_path))
+            .unwrap_or_default();
+        let runner = Runner::detect(&settings.peek().components_directory);
+
+        let mut settings = settings.write();
+        let installed = settings
+            .installed_games
+            .entry(game.id.clone())
+            .or_insert_with(|| InstalledGame {
+                id: game.id.clone(),
+                biz_name: game.biz.clone(),
+                install_path: install_path.clone(),
+                executable_path: executable_path.clone(),
+                command_wrapper: None,
+                command_arguments: None,
+                environment: HashMap::new(),
+                runner,
+                runtime_components: Vec::new(),
+                version: None,
+                server: None,
+                channel: Channel::Official,
+                extras: HashMap::new(),
+                umu_id: None,
+            });
+        installed.version = Some(release.version);
+        installed.server = Some(package_id.clone());
+        installed.executable_path = executable_path;
+        settings.save();
+    }
+
+    // Channel SDKs and plugins are checked on every update, even when the
+    // game itself is up to date
+    let Some(installed) = settings.peek().installed_games.get(&game.id).cloned() else {
+        return Ok(());
+    };
+    let mut extras = Vec::new();
+    match get_channel_sdk(&package_id, installed.channel, CachePolicy::Bypass).await {
+        Ok(sdk) => extras.extend(sdk.map(Extra::from)),
+        Err(e) => println!("Failed to load channel SDK for {name}: {e}"),
+    }
+    match get_game_plugins(&package_id, CachePolicy::Bypass).await {
+        Ok(plugins) => extras.extend(plugins.into_iter().map(Extra::from)),
+        Err(e) => println!("Failed to load plugins for {name}: {e}"),
+    }
+
+    let mut versions = installed.extras.clone();
+    let result = install::update_extras(
+        extras,
+        &mut versions,
+        &installed,
+        &temp_dir,
+        &endpoints,
+        &extraction,
+    )
+    .await;
+
+    // Extras installed before a failure are still recorded
+    {
+        let mut settings = settings.write();
+        if let Some(installed) = settings.installed_games.get_mut(&game.id) {
+            installed.extras = versions;
+        }
+        settings.save();
+    }
+
+    result.map_err(|e| format!("Failed to install extras for {name}: {e}"))?;
+    println!("{name} is up to date.");
+
+    Ok(())
+}
+
+async fn install_kuro_game(
+    game: kuro::Game,
+    install_path: PathBuf,
+    mut settings: Signal<GlobalSettings>,
+) -> Result<(), String> {
+    let endpoints = settings.peek().endpoints.clone();
+
+    println!("Installing {} {}..", game.name, game.index.default.version);
+    kuro::install_game(&game.index.default, &install_path, &endpoints)
+        .await
+        .map_err(|e| format!("Failed to install {}: {e}", game.name))?;
+
+    let version = game.index.default.version.clone();
+    let runner = Runner::detect(&settings.peek().components_directory);
+    let mut settings = settings.write();
+    settings
+        .installed_games
+        .entry(game.id.clone())
+        .or_insert_with(|| InstalledGame {
+            id: game.id.clone(),
+            biz_name: game.biz_name.clone(),
+            install_path,
+            executable_path: game.executable_path.clone(),
+            command_wrapper: None,
+            command_arguments: None,
+            environment: HashMap::new(),
+            runner,
+            runtime_components: Vec::new(),
+            version: None,
+            server: None,
+            channel: Channel::Official,
+            extras: HashMap::new(),
+            umu_id: None,
+        })
+        .version = Some(version);
+    settings.save();
+
+    println!("{} is up to date.", game.name);
+
+    Ok(())
+}
@@ -0,0 +1,180 @@
+use std::{fs, io::Read, path::Path};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256, Sha512};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Checksum {
+    Sha256(String),
+    Sha512(String),
+}
+
+impl Checksum {
+    /// Picks the algorithm from the length of a hex digest.
+    pub fn from_hex(hex: &str) -> Option<Checksum> {
+        let hex = hex.trim().to_lowercase();
+        if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+
+        match hex.len() {
+            64 => Some(Checksum::Sha256(hex)),
+            128 => Some(Checksum::Sha512(hex)),
+            _ => None,
+        }
+    }
+
+    /// Hashes `path` with the same algorithm.
+    pub fn of_file(&self, path: &Path) -> Result<Checksum, String> {
+        match self {
+            Checksum::Sha256(_) => sha256_file(path).map(Checksum::Sha256),
+            Checksum::Sha512(_) => hash_file::<Sha512>(path).map(Checksum::Sha512),
+        }
+    }
+}
+
+/// Fails unless `path` hashes to `expected`.
+pub fn verify_file(path: &Path, expected: &Checksum) -> Result<(), String> {
+    let actual = expected.of_file(path)?;
+    if actual != *expected {
+        return Err(format!(
+            "Checksum mismatch for {path:?}, expected {expected:?} but got {actual:?}"
+        ));
+    }
+
+    Ok(())
+}
+
+/// Downloads a checksum file (`sha256sum`/`sha512sum` output) and returns the
+/// digest listed for `file_name`, or its only digest.
+pub async fn fetch_checksum(url: &str, file_name: &str) -> Result<Checksum, String> {
+    let response = reqwest::get(url)
+        .await
+        .map_err(|e| format!("Failed to download checksum {url}: {e}"))?;
+    if !response.status().is_success() {
+        return Err(format!(
+            "Checksum download failed with status: {}",
+            response.status()
+        ));
+    }
+    let text = response
+        .text()
+        .await
+        .map_err(|e| format!("Failed to read checksum {url}: {e}"))?;
+
+    parse_checksums(&text, file_name).ok_or_else(|| format!("No checksum for {file_name} in {url}"))
+}
+
+fn parse_checksums(text: &str, file_name: &str) -> Option<Checksum> {
+    let entries = text
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            let hex = parts.next()?;
+            // `sha256sum -b` prefixes binary files with `*`
+            let name = parts.next().map(|name| name.trim_start_matches('*'));
+            Some((Checksum::from_hex(hex)?, name))
+        })
+        .collect::<Vec<_>>();
+
+    match entries.as_slice() {
+        [(checksum, _)] => Some(checksum.clone()),
+        _ => entries
+            .into_iter()
+            .find(|(_, name)| name.is_some_and(|name| name.rsplit('/').next() == Some(file_name)))
+            .map(|(checksum, _)| checksum),
+    }
+}
+
+pub fn sha256_file(path: &Path) -> Result<String, String> {
+    hash_file::<Sha256>(path)
+}
+
+fn hash_file<D: Digest>(path: &Path) -> Result<String, String> {
+    let mut file = fs::File::open(path).map_err(|e| format!("Failed to open {path:?}: {e}"))?;
+
+    let mut hasher = D::new();
+    let mut buffer = vec![0; 1024 * 1024];
+    loop {
+        let read = file
+            .read(&mut buffer)
+            .map_err(|e| format!("Failed to read {path:?}: {e}"))?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHA256: &str = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+
+    #[test]
+    fn picks_the_checksum_of_the_file() {
+        let sha512 = "a".repeat(128);
+        let text = format!(
+            "{SHA256}  other.tar.gz\n{sha512} *GE-Proton10-15.tar.gz\n\nnot a checksum line\n"
+        );
+
+        assert_eq!(
+            parse_checksums(&text, "GE-Proton10-15.tar.gz"),
+            Some(Checksum::Sha512(sha512))
+        );
+        assert_eq!(
+            parse_checksums(&text, "other.tar.gz"),
+            Some(Checksum::Sha256(SHA256.to_string()))
+        );
+        assert_eq!(parse_checksums(&text, "missing.tar.gz"), None);
+    }
+
+    #[test]
+    fn takes_a_single_checksum_whatever_its_name() {
+        let text = format!("{}  ./build/dist/umu.tar\n", SHA256.to_uppercase());
+
+        assert_eq!(
+            parse_checksums(&text, "umu-launcher-1.2.9-zipapp.tar"),
+            Some(Checksum::Sha256(SHA256.to_string()))
+        );
+        assert_eq!(
+            parse_checksums(SHA256, "anything"),
+            Some(Checksum::Sha256(SHA256.to_string()))
+        );
+    }
+
+    #[test]
+    fn matches_names_listed_with_a_directory() {
+        let text = format!("{SHA256}  dist/a.tar\n{SHA256}  dist/b.tar\n");
+
+        assert!(parse_checksums(&text, "b.tar").is_some());
+        assert!(parse_checksums(&text, "dist").is_none());
+    }
+
+    #[test]
+    fn rejects_digests_of_unknown_length() {
+        assert_eq!(Checksum::from_hex("abc"), None);
+        assert_eq!(Checksum::from_hex(&"g".repeat(64)), None);
+        assert_eq!(parse_checksums("abc  file.tar", "file.tar"), None);
+    }
+
+    #[test]
+    fn verifies_files_against_their_checksum() {
+        let path = std::env::temp_dir().join(format!("elysia-integrity-{}", std::process::id()));
+        fs::write(&path, "test").unwrap();
+
+        let matching = verify_file(&path, &Checksum::Sha256(SHA256.to_string()));
+        let mismatched = verify_file(&path, &Checksum::Sha256("0".repeat(64)));
+        fs::remove_file(&path).unwrap();
+
+        assert!(matching.is_ok());
+        assert!(mismatched.is_err());
+    }
+}
@@ -1,2 +1,11 @@
+pub mod cache;
+pub mod components;
+pub mod download;
+pub mod extract;
 pub mod filesystem;
-pub mod umu;
\ No newline at end of file
+pub mod installs;
+pub mod integrity;
+pub mod process;
+pub mod releases;
+pub mod umu;
+pub mod umu_database;
@@ -0,0 +1,22 @@
+use std::fs;
+
+/// Whether a process was started from a file named `executable`. Wine keeps
+/// the Windows path of the game in its command line, so this also finds games
+/// running through Wine or Proton.
+pub fn is_running(executable: &str) -> bool {
+    let Ok(entries) = fs::read_dir("/proc") else {
+        return false;
+    };
+
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_name().to_string_lossy().parse::<u32>().is_ok())
+        .filter_map(|entry| fs::read(entry.path().join("cmdline")).ok())
+        .any(|cmdline| {
+            cmdline
+                .split(|byte| *byte == 0)
+                .filter_map(|argument| std::str::from_utf8(argument).ok())
+                .filter_map(|argument| argument.rsplit(['/', '\\']).next())
+                .any(|name| name.eq_ignore_ascii_case(executable))
+        })
+}
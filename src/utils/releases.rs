@@ -0,0 +1,235 @@
+//! Wine and Proton builds, DXVK and VKD3D-Proton from their GitHub releases.
+//!
+//! Every build is installed through `components`, so it's only unpacked once
+//! it matches a checksum published with the release: a `.sha512sum` or
+//! `.sha256sum` file next to the archive, or else the digest GitHub lists for
+//! the asset. Releases with neither are refused.
+
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::{
+    runners::installed_versions,
+    utils::{
+        components::{ChecksumSource, ComponentKind, ComponentRelease, install_component},
+        integrity::Checksum,
+    },
+};
+
+/// Kinds installed from GitHub releases, in the order they're listed.
+pub const RELEASE_KINDS: [ComponentKind; 4] = [
+    ComponentKind::Proton,
+    ComponentKind::Wine,
+    ComponentKind::Dxvk,
+    ComponentKind::Vkd3dProton,
+];
+
+#[derive(Debug, Deserialize)]
+struct GithubRelease {
+    tag_name: String,
+    assets: Vec<GithubAsset>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GithubAsset {
+    name: String,
+    browser_download_url: String,
+    /// Like `sha256:<hex>`, missing on assets uploaded before GitHub had them
+    #[serde(default)]
+    digest: Option<String>,
+}
+
+/// Repository the builds of `kind` are released in.
+fn repository(kind: ComponentKind) -> &'static str {
+    match kind {
+        ComponentKind::Umu => "Open-Wine-Components/umu-launcher",
+        ComponentKind::Proton => "GloriousEggroll/proton-ge-custom",
+        ComponentKind::Wine => "GloriousEggroll/wine-ge-custom",
+        ComponentKind::Dxvk => "doitsujin/dxvk",
+        ComponentKind::Vkd3dProton => "HansKristian-Work/vkd3d-proton",
+    }
+}
+
+/// Whether `name` is the archive to install, releases also carry debug
+/// symbols and builds for other platforms.
+fn is_archive(kind: ComponentKind, name: &str) -> bool {
+    match kind {
+        ComponentKind::Umu => name.ends_with("-zipapp.tar"),
+        ComponentKind::Proton => name.ends_with(".tar.gz"),
+        ComponentKind::Wine => name.ends_with(".tar.xz") && !name.starts_with("debug-"),
+        ComponentKind::Dxvk => name.ends_with(".tar.gz") && !name.starts_with("dxvk-native"),
+        ComponentKind::Vkd3dProton => name.ends_with(".tar.zst"),
+    }
+}
+
+/// A file every build of `kind` has, relative to the build directory.
+fn required_file(kind: ComponentKind) -> PathBuf {
+    match kind {
+        ComponentKind::Umu => PathBuf::from("umu/umu-run"),
+        ComponentKind::Proton => PathBuf::from("proton"),
+        ComponentKind::Wine => PathBuf::from("bin/wine"),
+        ComponentKind::Dxvk => PathBuf::from("x64/d3d11.dll"),
+        ComponentKind::Vkd3dProton => PathBuf::from("x64/d3d12.dll"),
+    }
+}
+
+/// Looks up the latest release of `kind`.
+pub async fn latest_release(kind: ComponentKind) -> Result<ComponentRelease, String> {
+    let url = format!(
+        "https://api.github.com/repos/{}/releases/latest",
+        repository(kind)
+    );
+    let release = reqwest::Client::new()
+        .get(&url)
+        // GitHub rejects requests without one
+        .header(reqwest::header::USER_AGENT, "elysia")
+        .send()
+        .await
+        .map_err(|e| format!("Failed to fetch latest {kind} release: {e}"))?
+        .error_for_status()
+        .map_err(|e| format!("Failed to fetch latest {kind} release: {e}"))?
+        .json::<GithubRelease>()
+        .await
+        .map_err(|e| format!("Failed to parse latest {kind} release: {e}"))?;
+
+    component_release(kind, &release)
+}
+
+/// Installs the latest release of `kind` unless it's already there, and
+/// returns its version.
+pub async fn install_latest(
+    kind: ComponentKind,
+    components_directory: &Path,
+    temp_directory: &Path,
+) -> Result<String, String> {
+    let release = latest_release(kind).await?;
+    let installed = installed_versions(&components_directory.join(kind.to_string()));
+    if !installed.contains(&release.version) {
+        install_component(&release, components_directory, temp_directory).await?;
+    }
+
+    Ok(release.version)
+}
+
+fn component_release(
+    kind: ComponentKind,
+    release: &GithubRelease,
+) -> Result<ComponentRelease, String> {
+    let version = release.tag_name.trim_start_matches('v').to_string();
+    let archive = release
+        .assets
+        .iter()
+        .find(|asset| is_archive(kind, &asset.name))
+        .ok_or_else(|| format!("No {kind} archive in release {}", release.tag_name))?;
+
+    // Named after the archive, with or without its extension
+    let stem = archive.name.split(".tar").next().unwrap_or_default();
+    let checksum_file = [".sha512sum", ".sha256sum"].iter().find_map(|extension| {
+        release
+            .assets
+            .iter()
+            .find(|asset| asset.name.starts_with(stem) && asset.name.ends_with(extension))
+    });
+    let checksum = match (checksum_file, &archive.digest) {
+        (Some(file), _) => ChecksumSource::File(file.browser_download_url.clone()),
+        (None, Some(digest)) => digest
+            .strip_prefix("sha256:")
+            .and_then(Checksum::from_hex)
+            .map(ChecksumSource::Known)
+            .ok_or_else(|| format!("Unsupported digest for {}: {digest}", archive.name))?,
+        (None, None) => {
+            return Err(format!(
+                "Refusing to install {kind} {version}, it has no published checksum"
+            ));
+        }
+    };
+
+    let extract_to = PathBuf::from(kind.to_string()).join(&version);
+
+    Ok(ComponentRelease {
+        kind,
+        version,
+        url: archive.browser_download_url.clone(),
+        checksum,
+        files: vec![extract_to.join(required_file(kind))],
+        extract_to,
+        unwrap_directory: true,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DXVK_RELEASE: &str = r#"{
+        "tag_name": "v2.4",
+        "assets": [
+            {
+                "name": "dxvk-native-2.4-steamrt-sniper.tar.gz",
+                "browser_download_url": "https://github.com/doitsujin/dxvk/releases/download/v2.4/dxvk-native-2.4-steamrt-sniper.tar.gz",
+                "digest": "sha256:1111111111111111111111111111111111111111111111111111111111111111"
+            },
+            {
+                "name": "dxvk-2.4.tar.gz",
+                "browser_download_url": "https://github.com/doitsujin/dxvk/releases/download/v2.4/dxvk-2.4.tar.gz",
+                "digest": "sha256:2222222222222222222222222222222222222222222222222222222222222222"
+            }
+        ]
+    }"#;
+
+    const PROTON_RELEASE: &str = r#"{
+        "tag_name": "GE-Proton10-15",
+        "assets": [
+            {
+                "name": "GE-Proton10-15.sha512sum",
+                "browser_download_url": "https://github.com/GloriousEggroll/proton-ge-custom/releases/download/GE-Proton10-15/GE-Proton10-15.sha512sum"
+            },
+            {
+                "name": "GE-Proton10-15.tar.gz",
+                "browser_download_url": "https://github.com/GloriousEggroll/proton-ge-custom/releases/download/GE-Proton10-15/GE-Proton10-15.tar.gz"
+            }
+        ]
+    }"#;
+
+    fn release(json: &str) -> GithubRelease {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn verifies_against_the_asset_digest() {
+        let release = component_release(ComponentKind::Dxvk, &release(DXVK_RELEASE)).unwrap();
+
+        assert_eq!(release.version, "2.4");
+        assert!(release.url.ends_with("/dxvk-2.4.tar.gz"));
+        assert!(matches!(
+            release.checksum,
+            ChecksumSource::Known(Checksum::Sha256(hex)) if hex == "2".repeat(64)
+        ));
+        assert_eq!(release.extract_to, Path::new("dxvk/2.4"));
+        assert_eq!(release.files, [Path::new("dxvk/2.4/x64/d3d11.dll")]);
+    }
+
+    #[test]
+    fn prefers_published_checksum_files() {
+        let mut github_release = release(PROTON_RELEASE);
+        github_release.assets[1].digest = Some(format!("sha256:{}", "3".repeat(64)));
+        let release = component_release(ComponentKind::Proton, &github_release).unwrap();
+
+        assert_eq!(release.version, "GE-Proton10-15");
+        assert!(matches!(
+            release.checksum,
+            ChecksumSource::File(url) if url.ends_with("/GE-Proton10-15.sha512sum")
+        ));
+        assert_eq!(release.files, [Path::new("proton/GE-Proton10-15/proton")]);
+    }
+
+    #[test]
+    fn refuses_releases_without_checksums() {
+        let mut github_release = release(PROTON_RELEASE);
+        github_release.assets.remove(0);
+
+        let error = component_release(ComponentKind::Proton, &github_release).unwrap_err();
+        assert!(error.contains("no published checksum"));
+    }
+}
@@ -1,73 +1,236 @@
-use std::fs;
-use std::path::{Path, PathBuf};
-
+//! umu-launcher install and updates.
+//!
+//! Every version lives in `umu-versions/<version>/umu`, and `umu` in the
+//! components directory is a symlink to the one in use. Switching versions
+//! replaces that symlink in one rename, so a half installed update is never
+//! used, and the previous version is kept around for rollback.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use dioxus::prelude::consume_context;
 use freya::prelude::{Readable, Signal};
-use crate::settings::GlobalSettings;
-use crate::utils::filesystem::ensure_dir;
+use serde::Deserialize;
+
+use crate::{
+    settings::GlobalSettings,
+    utils::{
+        components::{
+            ChecksumSource, ComponentKind, ComponentManifest, ComponentRelease, install_component,
+            verify_installed,
+        },
+        filesystem::ensure_dir,
+    },
+};
+
+/// Used when the latest release can't be looked up and nothing is installed
+const FALLBACK_VERSION: &str = "1.2.9";
+const LATEST_RELEASE_URL: &str =
+    "https://api.github.com/repos/Open-Wine-Components/umu-launcher/releases/latest";
+const VERSIONS_DIRECTORY: &str = "umu-versions";
+
+#[derive(Deserialize)]
+struct GithubRelease {
+    tag_name: String,
+}
 
-const UMU_VERSION: &str = "1.2.9";
+/// Path of `umu-run` for the version in use.
+pub fn umu_run_path(components_directory: &Path) -> PathBuf {
+    components_directory.join("umu").join("umu-run")
+}
 
+/// Makes sure the pinned version, or the latest release, is installed and in use.
 pub async fn setup_umu() -> Result<PathBuf, String> {
-    let ctx = &dioxus::hooks::use_context::<Signal<GlobalSettings>>();
-    let settings = &ctx.read();
-
-    let umu_dir = settings.components_directory.join("umu");
-    let umu_run = umu_dir.join("umu-run");
+    let settings = consume_context::<Signal<GlobalSettings>>();
+    let components_directory = settings.read().components_directory.clone();
+    let temp_directory = settings.read().temp_directory.clone();
+    let pinned = settings.read().umu_version.clone();
+
+    migrate_legacy_install(&components_directory)?;
+    let current = current_version(&components_directory);
+
+    let target = match pinned {
+        Some(version) => version,
+        None => match latest_version().await {
+            Ok(version) => version,
+            Err(e) => {
+                println!("Cannot check for umu-launcher updates: {e}");
+                current
+                    .clone()
+                    .unwrap_or_else(|| FALLBACK_VERSION.to_string())
+            }
+        },
+    };
+
+    let verified = verify_installed(&components_directory, ComponentKind::Umu, &target);
+    if current.as_ref() == Some(&target) && verified.is_ok() {
+        println!("umu-launcher {target} found, skipping..");
+        return Ok(umu_run_path(&components_directory));
+    }
 
-    if umu_run.exists() {
-        println!("umu-launcher found, skipping..");
-        return Ok(umu_run);
+    if let Err(e) = verified {
+        println!("umu-launcher {target} not installed or failed verification, downloading: {e}");
+        install_component(
+            &umu_release(&target),
+            &components_directory,
+            &temp_directory,
+        )
+        .await
+        .map_err(|e| format!("Failed to install umu-launcher {target}: {e}"))?;
     }
 
-    println!("umu-launcher not found, downloading..");
-    ensure_dir(&settings.temp_directory)?;
+    switch_version(&components_directory, &target)?;
+    println!("Now using umu-launcher {target}.");
 
-    let archive_path = download_umu(&settings.temp_directory).await?;
-    extract_umu(&archive_path, &settings.components_directory)?;
-    let _ = fs::remove_file(&archive_path);
+    prune_versions(&components_directory, &[Some(target), current]);
 
-    if !umu_run.exists() {
-        return Err(format!("umu-run not found at {umu_run:?} after extraction"));
-    }
+    Ok(umu_run_path(&components_directory))
+}
+
+/// Goes back to the newest other installed version.
+pub fn rollback_umu(components_directory: &Path) -> Result<String, String> {
+    let current = current_version(components_directory);
+    let previous = installed_versions(components_directory)
+        .into_iter()
+        .rev()
+        .find(|version| Some(version) != current.as_ref())
+        .ok_or_else(|| "No other umu-launcher version to roll back to".to_string())?;
+
+    verify_installed(components_directory, ComponentKind::Umu, &previous)?;
+    switch_version(components_directory, &previous)?;
+
+    Ok(previous)
+}
+
+/// Version the `umu` symlink points to.
+pub fn current_version(components_directory: &Path) -> Option<String> {
+    let target = fs::read_link(components_directory.join("umu")).ok()?;
+
+    // umu-versions/<version>/umu
+    target
+        .parent()?
+        .file_name()
+        .map(|version| version.to_string_lossy().to_string())
+}
 
-    println!("umu-launcher download complete.");
+/// Versions in `umu-versions`, oldest first.
+pub fn installed_versions(components_directory: &Path) -> Vec<String> {
+    crate::runners::installed_versions(&components_directory.join(VERSIONS_DIRECTORY))
+}
+
+async fn latest_version() -> Result<String, String> {
+    let client = reqwest::Client::new();
+    let release = client
+        .get(LATEST_RELEASE_URL)
+        // GitHub rejects requests without one
+        .header(reqwest::header::USER_AGENT, "elysia")
+        .send()
+        .await
+        .map_err(|e| format!("Failed to fetch latest release: {e}"))?
+        .error_for_status()
+        .map_err(|e| format!("Failed to fetch latest release: {e}"))?
+        .json::<GithubRelease>()
+        .await
+        .map_err(|e| format!("Failed to parse latest release: {e}"))?;
 
-    Ok(umu_run)
+    Ok(release.tag_name.trim_start_matches('v').to_string())
 }
 
-async fn download_umu(temp_dir: &Path) -> Result<PathBuf, String> {
+fn umu_release(version: &str) -> ComponentRelease {
     let url = format!(
         "https://github.com/Open-Wine-Components/umu-launcher/releases/download/{0}/umu-launcher-{0}-zipapp.tar",
-        UMU_VERSION
+        version
     );
+    let extract_to = Path::new(VERSIONS_DIRECTORY).join(version);
+
+    ComponentRelease {
+        kind: ComponentKind::Umu,
+        version: version.to_string(),
+        checksum: ChecksumSource::File(format!("{url}.sha512sum")),
+        url,
+        // The archive holds a single `umu` directory
+        files: vec![extract_to.join("umu").join("umu-run")],
+        extract_to,
+        unwrap_directory: false,
+    }
+}
 
-    let archive_path = temp_dir.join(format!("umu-launcher-{}-zipapp.tar", UMU_VERSION));
+/// Points the `umu` symlink to another installed version.
+#[cfg(unix)]
+fn switch_version(components_directory: &Path, version: &str) -> Result<(), String> {
+    let link = components_directory.join("umu");
+    let new_link = components_directory.join("umu.new");
+    let target = Path::new(VERSIONS_DIRECTORY).join(version).join("umu");
+
+    let _ = fs::remove_file(&new_link);
+    std::os::unix::fs::symlink(&target, &new_link)
+        .map_err(|e| format!("Cannot link {new_link:?} to {target:?}: {e}"))?;
+    fs::rename(&new_link, &link).map_err(|e| format!("Cannot replace {link:?}: {e}"))
+}
 
-    let resp = reqwest::get(&url)
-        .await
-        .map_err(|e| format!("Failed to download umu-launcher: {e}"))?;
+#[cfg(not(unix))]
+fn switch_version(_components_directory: &Path, _version: &str) -> Result<(), String> {
+    Err("umu-launcher is only supported on Linux".to_string())
+}
 
-    if !resp.status().is_success() {
-        return Err(format!("Download failed with status: {}", resp.status()));
+/// Versions before this layout were unpacked straight into `umu`, and could
+/// only be the fallback version.
+fn migrate_legacy_install(components_directory: &Path) -> Result<(), String> {
+    let legacy = components_directory.join("umu");
+    let is_directory = fs::symlink_metadata(&legacy).is_ok_and(|metadata| metadata.is_dir());
+    if !is_directory {
+        return Ok(());
     }
 
-    let bytes = resp.bytes()
-        .await
-        .map_err(|e| format!("Failed to read umu-launcher bytes: {e}"))?;
-
-    fs::write(&archive_path, &bytes)
-        .map_err(|e| format!("Failed to write archive to {archive_path:?}: {e}"))?;
+    let versions = components_directory.join(VERSIONS_DIRECTORY);
+    let destination = versions.join(FALLBACK_VERSION);
+    ensure_dir(&destination)?;
+    fs::rename(&legacy, destination.join("umu"))
+        .map_err(|e| format!("Cannot move {legacy:?} to {destination:?}: {e}"))?;
+
+    let mut manifest = ComponentManifest::load(components_directory);
+    if let Some(record) = manifest.get_mut(ComponentKind::Umu, FALLBACK_VERSION) {
+        record.files = record
+            .files
+            .drain()
+            .map(|(file, hash)| {
+                let file = match file.strip_prefix("umu") {
+                    Ok(relative) => Path::new(VERSIONS_DIRECTORY)
+                        .join(FALLBACK_VERSION)
+                        .join("umu")
+                        .join(relative),
+                    Err(_) => file.clone(),
+                };
+                (file, hash)
+            })
+            .collect();
+        manifest.save(components_directory)?;
+    }
 
-    Ok(archive_path)
+    switch_version(components_directory, FALLBACK_VERSION)
 }
 
-fn extract_umu(archive_path: &Path, dest_dir: &Path) -> Result<(), String> {
-    let file = fs::File::open(archive_path)
-        .map_err(|e| format!("Failed to open archive {archive_path:?}: {e}"))?;
-
-    let mut archive = tar::Archive::new(file);
-    archive.unpack(dest_dir)
-        .map_err(|e| format!("Failed to extract archive to {dest_dir:?}: {e}"))?;
+/// Removes versions that aren't in `keep`.
+fn prune_versions(components_directory: &Path, keep: &[Option<String>]) {
+    let mut manifest = ComponentManifest::load(components_directory);
+    for version in installed_versions(components_directory) {
+        if keep.iter().flatten().any(|kept| *kept == version) {
+            continue;
+        }
+
+        let path = components_directory.join(VERSIONS_DIRECTORY).join(&version);
+        match fs::remove_dir_all(&path) {
+            Ok(()) => {
+                manifest.remove(ComponentKind::Umu, &version);
+                println!("Removed umu-launcher {version}");
+            }
+            Err(e) => println!("Cannot remove old umu-launcher {path:?}: {e}"),
+        }
+    }
 
-    Ok(())
+    if let Err(e) = manifest.save(components_directory) {
+        println!("{e}");
+    }
 }
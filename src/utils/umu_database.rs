@@ -0,0 +1,234 @@
+//! Lookup of umu game ids, which decide the protonfixes umu applies.
+//!
+//! The umu database is a CSV maintained next to protonfixes. It's refreshed in
+//! the background at startup and read from the cache when a game is started,
+//! so starting a game never waits on the network.
+
+use std::{path::Path, time::Duration};
+
+use serde::Deserialize;
+
+use crate::{
+    settings::InstalledGame,
+    utils::cache::{CacheEntry, read_entry, write_entry},
+};
+
+const DATABASE_URL: &str =
+    "https://raw.githubusercontent.com/Open-Wine-Components/umu-database/main/umu-database.csv";
+const DATABASE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+/// Runs the game without any protonfixes
+pub const DEFAULT_GAME_ID: &str = "umu-default";
+/// Store of games that aren't sold through a store umu knows about
+pub const NO_STORE: &str = "none";
+/// umu ids of games whose biz name isn't a codename in the database
+const KNOWN_GAMES: [(&str, &str); 3] = [
+    ("hk4e_global", "umu-genshin"),
+    ("hkrpg_global", "umu-honkaistarrail"),
+    ("nap_global", "umu-zenlesszonezero"),
+];
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct UmuGame {
+    #[serde(rename = "TITLE")]
+    pub title: String,
+    #[serde(rename = "STORE")]
+    pub store: String,
+    #[serde(rename = "CODENAME")]
+    pub codename: String,
+    #[serde(rename = "UMU_ID")]
+    pub umu_id: String,
+    /// Executable names separated by commas
+    #[serde(rename = "EXE_STRINGS (Optional)", default)]
+    pub exe_strings: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UmuId {
+    pub game_id: String,
+    pub store: String,
+}
+
+/// Downloads the database again once the cached copy is older than a day.
+pub async fn refresh(cache_path: &Path) -> Result<(), String> {
+    if read_entry::<String>(cache_path, DATABASE_URL)
+        .is_some_and(|entry| entry.is_fresh(DATABASE_TTL))
+    {
+        return Ok(());
+    }
+
+    let response = reqwest::get(DATABASE_URL)
+        .await
+        .map_err(|e| format!("Failed to download the umu database: {e}"))?;
+    if !response.status().is_success() {
+        return Err(format!(
+            "umu database download failed with status: {}",
+            response.status()
+        ));
+    }
+    let headers = response.headers().clone();
+    let text = response
+        .text()
+        .await
+        .map_err(|e| format!("Failed to read the umu database: {e}"))?;
+
+    // Don't replace a working copy with something that doesn't parse
+    parse(&text)?;
+    write_entry(cache_path, DATABASE_URL, &CacheEntry::new(text, &headers));
+
+    Ok(())
+}
+
+/// The cached database, empty when it was never downloaded.
+pub fn load_cached(cache_path: &Path) -> Vec<UmuGame> {
+    let Some(entry) = read_entry::<String>(cache_path, DATABASE_URL) else {
+        return Vec::new();
+    };
+
+    parse(&entry.data).unwrap_or_else(|e| {
+        println!("{e}");
+        Vec::new()
+    })
+}
+
+fn parse(text: &str) -> Result<Vec<UmuGame>, String> {
+    csv::Reader::from_reader(text.as_bytes())
+        .deserialize()
+        .collect::<Result<Vec<UmuGame>, _>>()
+        .map_err(|e| format!("Failed to parse the umu database: {e}"))
+}
+
+/// umu id of an installed game: its override, the id we know for its biz
+/// name, or the database entry matching its biz name or executable, preferring
+/// entries outside of any store.
+pub fn resolve(database: &[UmuGame], game: &InstalledGame) -> Option<UmuId> {
+    if let Some(game_id) = game.umu_id.as_ref().filter(|id| !id.is_empty()) {
+        return Some(UmuId {
+            game_id: game_id.clone(),
+            store: NO_STORE.to_string(),
+        });
+    }
+
+    if let Some((_, game_id)) = KNOWN_GAMES
+        .iter()
+        .find(|(biz_name, _)| *biz_name == game.biz_name)
+    {
+        let store = best_match(database.iter().filter(|entry| entry.umu_id == *game_id))
+            .map(|entry| entry.store.to_lowercase())
+            .unwrap_or_else(|| NO_STORE.to_string());
+        return Some(UmuId {
+            game_id: game_id.to_string(),
+            store,
+        });
+    }
+
+    let executable = game
+        .executable_path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_lowercase());
+    let matches = database
+        .iter()
+        .filter(|entry| !entry.umu_id.is_empty())
+        .filter(|entry| {
+            entry.codename.eq_ignore_ascii_case(&game.biz_name)
+                || executable.as_ref().is_some_and(|executable| {
+                    entry
+                        .exe_strings
+                        .split(',')
+                        .any(|name| name.trim().eq_ignore_ascii_case(executable))
+                })
+        });
+
+    best_match(matches).map(|entry| UmuId {
+        game_id: entry.umu_id.clone(),
+        store: entry.store.to_lowercase(),
+    })
+}
+
+/// First of `entries` outside of any store, or else the first one.
+fn best_match<'a>(entries: impl Iterator<Item = &'a UmuGame>) -> Option<&'a UmuGame> {
+    let mut entries = entries.collect::<Vec<_>>();
+    entries.sort_by_key(|entry| !entry.store.eq_ignore_ascii_case(NO_STORE));
+
+    entries.first().copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, fs, path::PathBuf};
+
+    use super::*;
+    use crate::{runners::Runner, settings::Channel};
+
+    fn database() -> Vec<UmuGame> {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/umu/umu-database.csv");
+        parse(&fs::read_to_string(path).unwrap()).unwrap()
+    }
+
+    fn game(biz_name: &str, executable: &str) -> InstalledGame {
+        InstalledGame {
+            id: biz_name.to_string(),
+            biz_name: biz_name.to_string(),
+            install_path: PathBuf::from("/games").join(biz_name),
+            executable_path: PathBuf::from(executable),
+            command_wrapper: None,
+            command_arguments: None,
+            environment: HashMap::new(),
+            runner: Runner::Native,
+            runtime_components: Vec::new(),
+            version: None,
+            server: None,
+            channel: Channel::Official,
+            extras: HashMap::new(),
+            umu_id: None,
+        }
+    }
+
+    fn umu_id(game_id: &str, store: &str) -> Option<UmuId> {
+        Some(UmuId {
+            game_id: game_id.to_string(),
+            store: store.to_string(),
+        })
+    }
+
+    #[test]
+    fn prefers_the_override() {
+        let mut game = game("hk4e_global", "GenshinImpact.exe");
+        game.umu_id = Some("umu-custom".to_string());
+
+        assert_eq!(resolve(&database(), &game), umu_id("umu-custom", NO_STORE));
+    }
+
+    #[test]
+    fn uses_known_ids_with_their_store() {
+        let database = database();
+
+        assert_eq!(
+            resolve(&database, &game("hk4e_global", "GenshinImpact.exe")),
+            umu_id("umu-genshin", NO_STORE)
+        );
+        assert_eq!(
+            resolve(&database, &game("hkrpg_global", "StarRail.exe")),
+            umu_id("umu-honkaistarrail", "egs")
+        );
+        // Missing from the database
+        assert_eq!(
+            resolve(&database, &game("nap_global", "ZenlessZoneZero.exe")),
+            umu_id("umu-zenlesszonezero", NO_STORE)
+        );
+    }
+
+    #[test]
+    fn falls_back_to_the_database() {
+        let database = database();
+
+        assert_eq!(
+            resolve(&database, &game("G153", "Wuthering Waves.exe")),
+            umu_id("umu-wutheringwaves", NO_STORE)
+        );
+        assert_eq!(
+            resolve(&database, &game("genshinimpact", "launcher.exe")),
+            umu_id("umu-genshin", NO_STORE)
+        );
+        assert_eq!(resolve(&database, &game("bh3_global", "BH3.exe")), None);
+    }
+}
@@ -0,0 +1,149 @@
+//! Runs the HoYoPlay api client against the mock server and the recorded
+//! fixtures.
+
+#[path = "../src/bin/mock_server.rs"]
+#[allow(dead_code)]
+mod mock_server;
+
+use std::{env, fs, net::TcpListener, path::PathBuf, process, sync::OnceLock, thread};
+
+use elysia::{
+    game_providers::hoyoplay::{self, api::ApiError},
+    globals::{paths, resolve_data_path},
+    settings::Endpoints,
+    utils::{cache::CachePolicy, download::md5_matches},
+};
+
+/// Started once and shared by every test
+static SERVER: OnceLock<()> = OnceLock::new();
+
+/// Cache and temp files of this run, through portable mode
+fn home() -> PathBuf {
+    env::temp_dir().join(format!("elysia-tests-{}", process::id()))
+}
+
+fn start_server() {
+    SERVER.get_or_init(|| {
+        let root = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures");
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        // SAFETY: nothing else runs yet, every test waits for this first
+        unsafe {
+            env::set_var("ELYSIA_HOME", home());
+            env::set_var("ELYSIA_HOYOPLAY_API_URL", format!("{url}/hoyoplay"));
+            env::set_var("ELYSIA_HOYOPLAY_CDN_URL", format!("{url}/cdn"));
+        }
+        resolve_data_path(None).unwrap();
+
+        // On its own runtime, the one of a test stops when the test ends
+        listener.set_nonblocking(true).unwrap();
+        thread::spawn(move || {
+            let runtime = tokio::runtime::Runtime::new().unwrap();
+            runtime.block_on(async move {
+                let listener = tokio::net::TcpListener::from_std(listener).unwrap();
+                mock_server::serve(listener, root).await;
+            });
+        });
+    });
+}
+
+#[tokio::test]
+async fn parses_games() {
+    start_server();
+
+    let response = hoyoplay::get_games(CachePolicy::Bypass).await.unwrap();
+    let ids = response
+        .games
+        .iter()
+        .map(|game| game.id.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        ids,
+        ["U5hbdsT9W7", "gopR6Cufr3", "pcAFixture", "hiddenFixt"]
+    );
+
+    let zzz = &response.games[0];
+    assert_eq!(zzz.biz, "nap_global");
+    assert!(zzz.display_status.is_released());
+    assert!(!response.games[2].display_status.is_released());
+    assert!(!response.games[3].display_status.is_visible());
+}
+
+#[tokio::test]
+async fn parses_game_content() {
+    start_server();
+
+    let content = hoyoplay::get_game_content("U5hbdsT9W7", CachePolicy::PreferCache)
+        .await
+        .unwrap()
+        .content;
+    assert_eq!(content.game.biz, "nap_global");
+    assert_eq!(content.banners.len(), 1);
+    assert_eq!(content.posts[0].title, "Fixture post");
+}
+
+#[tokio::test]
+async fn does_not_cache_server_errors() {
+    start_server();
+
+    // The second request would be answered from the cache if the first was kept
+    for _ in 0..2 {
+        let error = hoyoplay::get_game_content("gopR6Cufr3", CachePolicy::PreferCache)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error,
+            ApiError::Server {
+                retcode: -1,
+                message: "Server under maintenance".to_string(),
+            }
+        );
+    }
+
+    let cached = cacache::list_sync(paths().unwrap().cache)
+        .filter_map(Result::ok)
+        .any(|metadata| metadata.key.contains("gopR6Cufr3"));
+    assert!(!cached);
+}
+
+#[tokio::test]
+async fn reports_error_statuses_as_request_errors() {
+    start_server();
+
+    // The mock server has no content for this game and answers 404
+    let error = hoyoplay::get_game_content("missing", CachePolicy::Bypass)
+        .await
+        .unwrap_err();
+    assert!(matches!(error, ApiError::Request(_)), "{error:?}");
+}
+
+#[tokio::test]
+async fn downloads_packages_matching_their_checksum() {
+    start_server();
+
+    let packages = hoyoplay::get_game_packages("U5hbdsT9W7", CachePolicy::Bypass)
+        .await
+        .unwrap();
+    let release = packages.main.major.unwrap();
+    assert_eq!(release.version, "2.0.0");
+
+    let directory = home().join("packages");
+    fs::create_dir_all(&directory).unwrap();
+    for package in &release.game_pkgs {
+        let url = Endpoints::rewrite(&package.url, Endpoints::default().hoyoplay_cdn());
+        let body = reqwest::get(&url)
+            .await
+            .and_then(|response| response.error_for_status())
+            .unwrap()
+            .bytes()
+            .await
+            .unwrap();
+        assert_eq!(body.len().to_string(), package.size);
+
+        let name = url.rsplit('/').next().unwrap();
+        let path = directory.join(name);
+        fs::write(&path, &body).unwrap();
+        assert!(md5_matches(&path, &package.md5).unwrap());
+    }
+}